@@ -8,6 +8,7 @@ extern crate simd;
 use simd::x86::avx::f32x8;
 use canvas::plot::{Figure};
 use canvas::array::{Array, RowMajor};
+use canvas::sampler::Lanes;
 use tuple::*;
 use math::builder::Builder;
 use math::avx::avx_jit;
@@ -26,14 +27,12 @@ fn main() {
     // d/dx cos(x) * sin(y) = -sin(x) * sin(y)
     // d/dy cos(x) * sin(y) = cos(x) * cos(y)
     
-    for i in -9 ... 9i8 {
-        let c = f32x8::splat(i as f32);
-        canvas.contour(|T2(x, y): T2<f32, f32>| {
-            let vars = [f32x8::splat(x), f32x8::splat(y), c];
-            let T8(y, ..) = code.call(&vars).0.into();
-            y
-        });
-    }
+    // all levels in one pass, with c = 0, and eight points per call
+    let levels: Vec<f32> = (-9 ... 9i8).map(|i| i as f32 / 10.).collect();
+    let c = f32x8::splat(0.);
+    canvas.contour_levels(Lanes::new(|T2(x, y): T2<f32x8, f32x8>| {
+        code.call(&[x, y, c]).0
+    }), &levels, None);
         
     canvas.grayscale(None).save("data/contour.png").unwrap();
 }
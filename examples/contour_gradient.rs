@@ -6,7 +6,7 @@ extern crate simd;
 extern crate rand;
 extern crate math;
 
-use canvas::plot::{Figure};
+use canvas::plot::{Figure, Acceptance};
 use canvas::array::{Array, RowMajor};
 use canvas::rng::{VRng, DefaultRng};
 use tuple::T2;
@@ -79,15 +79,18 @@ fn main() {
     for i in -9 ... 9i8 {
         let c = f32x8::splat(i as f32);
         eprint!("{} ", i);
-        fig.contour_gradient(
+        fig.contour_gradient_with(
             |T2(x, y): T2<f32x8, f32x8>| {
                 let (p, px, py) = code.call(&[x, y, c]);
                 (p, T2(px, py))
             },
             100_000,
             10,
+            Acceptance::Threshold(1e-3),
             &mut rng
         );
+        let stats = fig.gradient_stats();
+        eprint!("({}/{}) ", stats.accepted, stats.samples);
     }
     eprintln!(" saving");
     fig.grayscale(None).save("data/contour_gradient.png").unwrap();
@@ -0,0 +1,25 @@
+#![feature(inclusive_range_syntax)]
+
+extern crate canvas;
+extern crate tuple;
+extern crate rusttype;
+
+use canvas::plot::{Figure};
+use canvas::array::{Array, RowMajor};
+use canvas::label::Labels;
+use rusttype::FontCollection;
+use tuple::*;
+
+fn main() {
+    let mut canvas: Figure<f32, Array<Vec<f32>, RowMajor>> = Figure::new(-4.0 .. 4.0, -4.0 .. 4.0, (1024, 1024));
+
+    let font = FontCollection::from_bytes(&include_bytes!("fonts/LiberationSerif-Regular.ttf")[..])
+        .into_font()
+        .expect("invalid font");
+    let labels = Labels::new(font, 16.0);
+
+    let levels: Vec<f32> = (-4 ... 4i8).map(|i| i as f32 / 5.).collect();
+    canvas.contour_labeled(|T2(x, y): T2<f32, f32>| x.cos() * y.sin(), &levels, &labels);
+
+    canvas.grayscale(None).save("data/contour_labels.png").unwrap();
+}
@@ -192,6 +192,17 @@ impl Array<Vec<u8>, RowMajor> {
         let (width, height) = self.meta.size();
         GrayImage::from_raw(width as u32, height as u32, self.data).unwrap()
     }
+    
+    /// The first row of the image is the top (y = height - 1), as in the plots of a `Figure`.
+    pub fn from_image(image: GrayImage) -> Self {
+        let (width, height) = image.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        let raw = image.into_raw();
+        Array {
+            meta:   RowMajor::new(width, height),
+            data:   (0 .. height).rev().flat_map(|y| raw[y * width .. (y + 1) * width].iter().cloned()).collect()
+        }
+    }
 }
 impl<D, M> Index<(usize, usize)> for Array<D, M> where D: Data, M: Meta
 {
@@ -1,8 +1,61 @@
-use tuple::{T2, T4, TupleElements, Map};
+use tuple::{T2, T4, TupleElements};
 use math::real::{Real};
 use math::cast::Cast;
 use std::fmt::Debug;
 use pen::Pen;
+use interval::Interval;
+use sampler::{Sampler, BATCH};
+use crossbeam;
+use std::collections::HashMap;
+use std::cmp;
+use std::cell::RefCell;
+use util::{max, min};
+
+// an edge of the grid: the lower left end, and whether it is horizontal
+type Edge = (usize, usize, bool);
+
+// a piece of the contour of one level, from one edge of a cell to another
+type Segment<N> = (usize, (Edge, T2<N, N>), (Edge, T2<N, N>));
+
+/// A contour line of one level.
+#[derive(Clone, Debug)]
+pub struct Polyline<N> {
+    pub points: Vec<T2<N, N>>,
+    /// the last point connects to the first one
+    pub closed: bool,
+    /// index of the level
+    pub level: usize
+}
+impl<N: Real> Polyline<N> {
+    pub fn length(&self) -> N {
+        let mut length = N::int(0);
+        for w in self.points.windows(2) {
+            length = length + dist(w[0], w[1]);
+        }
+        if self.closed && self.points.len() > 1 {
+            length = length + dist(self.points[self.points.len() - 1], self.points[0]);
+        }
+        length
+    }
+
+    /// Signed area enclosed by the line, positive if it runs counter-clockwise.
+    /// Open lines are closed with a straight line from the last point to the first one.
+    pub fn area(&self) -> N {
+        let n = self.points.len();
+        let mut sum = N::int(0);
+        for i in 0 .. n {
+            let T2(x0, y0) = self.points[i];
+            let T2(x1, y1) = self.points[(i + 1) % n];
+            sum = sum + x0 * y1 - x1 * y0;
+        }
+        sum / N::int(2)
+    }
+}
+fn dist<N: Real>(a: T2<N, N>, b: T2<N, N>) -> N {
+    let T2(dx, dy) = b - a;
+    (dx * dx + dy * dy).sqrt()
+}
+
 
 #[derive(Copy, Clone, Debug)]
 struct Cell<N: Debug> {
@@ -12,19 +65,67 @@ struct Cell<N: Debug> {
     f_tl: N, // f(x,   y  )
     f_tr: N, // f(x+d, y  )
     f_bl: N, // f(x,   y+d)
-    f_br: N  // f(x+d, y+d)
+    f_br: N, // f(x+d, y+d)
+    spread: Option<N> // difference of the largest and smallest corner value of the parent cell
 }
+
+// the four cells of a split
+type Quad<N> = T4<Cell<N>, Cell<N>, Cell<N>, Cell<N>>;
 impl<N: Debug> Cell<N> {
-    fn split<F>(self, f: F) -> T4<Cell<N>, Cell<N>, Cell<N>, Cell<N>>
-        where F: Fn(usize, usize) -> N, N: Copy
+    // smallest and largest corner value
+    fn range(&self) -> (N, N) where N: PartialOrd + Copy {
+        let mut lo = self.f_tl;
+        let mut hi = self.f_tl;
+        for &v in &[self.f_tr, self.f_bl, self.f_br] {
+            if v < lo { lo = v; }
+            if v > hi { hi = v; }
+        }
+        (lo, hi)
+    }
+    
+    // Sign changes along the edge with the values a and b may be poles.
+    // Towards a zero the values at the ends of an edge get closer, towards a pole they get further apart,
+    // so halving a cell makes the jump larger than the range of the parent only at a pole.
+    fn jumps(&self, a: N, b: N) -> bool where N: Real + PartialOrd {
+        match self.spread {
+            Some(spread) => !((a - b).abs() <= spread) || spread - spread != N::int(0),
+            None => true
+        }
+    }
+    
+    // no corner is NaN or infinite
+    fn finite(&self) -> bool where N: Real {
+        let zero = N::int(0);
+        [self.f_tl, self.f_tr, self.f_bl, self.f_br].iter().all(|&v| v - v == zero)
+    }
+    
+    // the same cell with c subtracted from all values
+    fn shift(self, c: N) -> Cell<N> where N: Real {
+        Cell {
+            f_tl: self.f_tl - c,
+            f_tr: self.f_tr - c,
+            f_bl: self.f_bl - c,
+            f_br: self.f_br - c,
+            .. self
+        }
+    }
+    
+    // the points a split needs: top, bottom, left, right and middle
+    fn split_points(&self) -> [(usize, usize); 5] {
+        let Cell { x, y, d, .. } = *self;
+        let d2 = d / 2;
+        [(x+d2, y+d), (x+d2, y), (x, y+d2), (x+d, y+d2), (x+d2, y+d2)]
+    }
+    
+    // `values` at the split points
+    fn split(self, values: &[N]) -> T4<Cell<N>, Cell<N>, Cell<N>, Cell<N>>
+        where N: Real + PartialOrd
     {
-        let Cell { x, y, d, f_tl, f_tr, f_bl, f_br } = self;
+        let (lo, hi) = self.range();
+        let Cell { x, y, d, f_tl, f_tr, f_bl, f_br, .. } = self;
         let d2 = d / 2;
-        let f_t = f(x+d2, y+d);
-        let f_b = f(x+d2, y);
-        let f_l = f(x,    y+d2);
-        let f_r = f(x+d,  y+d2);
-        let f_m = f(x+d2, y+d2);
+        let s = Some(hi - lo);
+        let (f_t, f_b, f_l, f_r, f_m) = (values[0], values[1], values[2], values[3], values[4]);
 
         //  y + d   f_tl   f_t   f_tr
         //              A      B
@@ -34,78 +135,995 @@ impl<N: Debug> Cell<N> {
         //
         //          x      x+d2  x+d
         T4(
-            Cell { x: x,    y: y+d2, d: d2, f_tl: f_tl, f_tr:  f_t,  f_bl: f_l,  f_br: f_m  }, // top-left (A)
-            Cell { x: x+d2, y: y+d2, d: d2, f_tl: f_t,  f_tr:  f_tr, f_bl: f_m,  f_br: f_r  }, // top-right (B)
-            Cell { x: x,    y: y,    d: d2, f_tl: f_l,  f_tr:  f_m,  f_bl: f_bl, f_br: f_b  }, // bottom-right (C)
-            Cell { x: x+d2, y: y,    d: d2, f_tl: f_m,  f_tr:  f_r,  f_bl: f_b,  f_br: f_br }  // bottom-right (D)
+            Cell { x: x,    y: y+d2, d: d2, f_tl: f_tl, f_tr:  f_t,  f_bl: f_l,  f_br: f_m,  spread: s }, // top-left (A)
+            Cell { x: x+d2, y: y+d2, d: d2, f_tl: f_t,  f_tr:  f_tr, f_bl: f_m,  f_br: f_r,  spread: s }, // top-right (B)
+            Cell { x: x,    y: y,    d: d2, f_tl: f_l,  f_tr:  f_m,  f_bl: f_bl, f_br: f_b,  spread: s }, // bottom-right (C)
+            Cell { x: x+d2, y: y,    d: d2, f_tl: f_m,  f_tr:  f_r,  f_bl: f_b,  f_br: f_br, spread: s }  // bottom-right (D)
         )
     }
 }
-pub struct ContourPlot<F, D> {
+/// Calls of the function of a `ContourPlot` during the last run.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ContourStats {
+    pub evaluations: usize,
+    /// grid points that were needed again, and taken from the cache
+    pub cache_hits: usize
+}
+
+// a borrowed sampler, for the threads of `run_parallel`
+struct Shared<'a, S: 'a>(&'a S);
+impl<'a, N, S: Sampler<N>> Sampler<N> for Shared<'a, S> {
+    fn sample(&self, p: T2<N, N>) -> N {
+        self.0.sample(p)
+    }
+    fn sample_batch(&self, points: &[T2<N, N>], out: &mut [N]) {
+        self.0.sample_batch(points, out)
+    }
+}
+impl<'a, S> Clone for Shared<'a, S> {
+    fn clone(&self) -> Shared<'a, S> {
+        Shared(self.0)
+    }
+}
+impl<'a, S> Copy for Shared<'a, S> {}
+
+/// Bounds of a function over a rectangle, see `ContourPlot::bound`.
+///
+/// Every `Fn(T2<Interval<N>, Interval<N>>) -> Interval<N>` is one.
+pub trait Bound<N> {
+    fn bound(&self, p: T2<Interval<N>, Interval<N>>) -> Interval<N>;
+}
+impl<N, F> Bound<N> for F where F: Fn(T2<Interval<N>, Interval<N>>) -> Interval<N> {
+    #[inline]
+    fn bound(&self, p: T2<Interval<N>, Interval<N>>) -> Interval<N> {
+        self(p)
+    }
+}
+
+/// Type of the bound of a `ContourPlot` without one.
+pub type NoBound<N> = fn(T2<Interval<N>, Interval<N>>) -> Interval<N>;
+
+pub struct ContourPlot<F, D, N: Real = f32, B = NoBound<N>> {
     pub search_depth: u8,
     pub plot_depth: u8,
-    pub pen: Pen<D>,
-    pub func: F
+    pub tiles: (usize, usize), // number of root cells in x and y direction
+    pub levels: Vec<(N, N)>,   // values to draw the contours of, and their weight
+    pub bands: Option<Vec<N>>, // fill the bands between the levels with these values instead (clipped to the grid, unless the pen is)
+    pub tolerance: Option<N>,  // refine the crossings to this distance (in grid steps)
+    pub pen: Pen<D, N>,
+    pub func: F,
+    
+    // bounds of func over a rectangle (in grid coordinates)
+    // cells are subdivided down to the plot depth as long as it contains a level
+    pub bound: Option<B>,
+    
+    // remember the values at grid points, to not evaluate them again for the neighbouring cells
+    pub memoize: bool,
+    
+    // collected by `polylines`
+    segments: Option<Vec<Segment<N>>>,
+    
+    cache: RefCell<HashMap<(usize, usize), N>>,
+    stats: RefCell<ContourStats>
 }
-impl<F, D, N> ContourPlot<F, D> where F: Fn(T2<usize, usize>) -> N, D: FnMut(T2<isize, isize>, f32), N: Real<Bool=bool> + Copy + Cast<f32>,
-usize: Cast<N>
+impl<F, D, N> ContourPlot<F, D, N> where F: Sampler<N>, D: FnMut(T2<isize, isize>, N),
+N: Real<Bool=bool> + PartialOrd + Copy + Cast<isize> + Cast<usize>,
+usize: Cast<N>, isize: Cast<N>
 {
+    /// A single root cell, searched down to half the plot depth, drawing the zero contour.
+    pub fn new(func: F, pen: Pen<D, N>, plot_depth: u8) -> ContourPlot<F, D, N> {
+        ContourPlot {
+            search_depth: plot_depth / 2,
+            plot_depth: plot_depth,
+            tiles: (1, 1),
+            levels: vec![(N::int(0), N::int(1))],
+            bands: None,
+            tolerance: None,
+            pen: pen,
+            func: func,
+            bound: None,
+            memoize: false,
+            segments: None,
+            cache: RefCell::new(HashMap::new()),
+            stats: RefCell::new(ContourStats::default())
+        }
+    }
+}
+impl<F, D, N, B> ContourPlot<F, D, N, B> where F: Sampler<N>, D: FnMut(T2<isize, isize>, N),
+B: Bound<N>,
+N: Real<Bool=bool> + PartialOrd + Copy + Cast<isize> + Cast<usize>,
+usize: Cast<N>, isize: Cast<N>
+{
+    /// Use `bound` to find contours that don't cross the corners of the cells above the plot depth.
+    pub fn with_bound<C>(self, bound: Option<C>) -> ContourPlot<F, D, N, C> {
+        ContourPlot {
+            search_depth: self.search_depth,
+            plot_depth: self.plot_depth,
+            tiles: self.tiles,
+            levels: self.levels,
+            bands: self.bands,
+            tolerance: self.tolerance,
+            pen: self.pen,
+            func: self.func,
+            bound: bound,
+            memoize: self.memoize,
+            segments: self.segments,
+            cache: self.cache,
+            stats: self.stats
+        }
+    }
+    
+    pub fn stats(&self) -> ContourStats {
+        *self.stats.borrow()
+    }
+    
+    fn call(&self, p: T2<N, N>) -> N {
+        self.stats.borrow_mut().evaluations += 1;
+        self.func.sample(p)
+    }
+    
+    // values at grid points, in batches of up to `BATCH`
+    fn eval(&self, points: &[(usize, usize)], out: &mut [N]) {
+        if points.len() > BATCH {
+            for (points, out) in points.chunks(BATCH).zip(out.chunks_mut(BATCH)) {
+                self.eval(points, out);
+            }
+            return;
+        }
+        
+        let zero = N::int(0);
+        let grid = |&(x, y): &(usize, usize)| -> T2<N, N> { T2(x, y).cast().unwrap() };
+        let mut batch = [T2(zero, zero); BATCH];
+        if !self.memoize {
+            for (b, p) in batch.iter_mut().zip(points) {
+                *b = grid(p);
+            }
+            self.stats.borrow_mut().evaluations += points.len();
+            self.func.sample_batch(&batch[.. points.len()], out);
+            return;
+        }
+        
+        // indices of the points not in the cache, and of the points that are in the batch already
+        let mut missing = [0; BATCH];
+        let mut repeated = [(0, 0); BATCH];
+        let (mut n, mut r) = (0, 0);
+        {
+            let cache = self.cache.borrow();
+            for (i, p) in points.iter().enumerate() {
+                match (cache.get(p), missing[.. n].iter().position(|&j| points[j] == *p)) {
+                    (Some(&v), _) => out[i] = v,
+                    (None, Some(k)) => {
+                        repeated[r] = (i, k);
+                        r += 1;
+                    },
+                    (None, None) => {
+                        missing[n] = i;
+                        batch[n] = grid(p);
+                        n += 1;
+                    }
+                }
+            }
+        }
+        self.stats.borrow_mut().cache_hits += points.len() - n;
+        if n > 0 {
+            let mut values = [zero; BATCH];
+            self.stats.borrow_mut().evaluations += n;
+            self.func.sample_batch(&batch[.. n], &mut values[.. n]);
+            
+            let mut cache = self.cache.borrow_mut();
+            for (&i, &v) in missing[.. n].iter().zip(values.iter()) {
+                out[i] = v;
+                cache.insert(points[i], v);
+            }
+            for &(i, k) in &repeated[.. r] {
+                out[i] = values[k];
+            }
+        }
+    }
+    
+    // root cell of tile (tx, ty)
+    fn root(&self, tx: usize, ty: usize) -> Cell<N> {
+        let d = 1 << self.plot_depth;
+        let (x, y) = (tx * d, ty * d);
+        let mut v = [N::int(0); 4];
+        self.eval(&[(x, y+d), (x+d, y+d), (x, y), (x+d, y)], &mut v);
+        Cell { x: x, y: y, d: d, f_tl: v[0], f_tr: v[1], f_bl: v[2], f_br: v[3], spread: None }
+    }
+    
+    fn split(&self, q: Cell<N>) -> T4<Cell<N>, Cell<N>, Cell<N>, Cell<N>> {
+        let mut v = [N::int(0); 5];
+        self.eval(&q.split_points(), &mut v);
+        q.split(&v)
+    }
+    
+    // split four cells with one batch of evaluations
+    fn split_siblings(&self, cells: Quad<N>) -> T4<Quad<N>, Quad<N>, Quad<N>, Quad<N>> {
+        let T4(a, b, c, d) = cells;
+        let mut points = [(0, 0); 20];
+        for (q, p) in [a, b, c, d].iter().zip(points.chunks_mut(5)) {
+            p.copy_from_slice(&q.split_points());
+        }
+        let mut v = [N::int(0); 20];
+        self.eval(&points, &mut v);
+        T4(a.split(&v[0 .. 5]), b.split(&v[5 .. 10]), c.split(&v[10 .. 15]), d.split(&v[15 .. 20]))
+    }
+    
+    // forget the values and counts of the last run
+    fn reset(&mut self) {
+        self.cache.borrow_mut().clear();
+        *self.stats.borrow_mut() = ContourStats::default();
+    }
+    
+    // Filling bands needs a clip rectangle. Without one, they are clipped to the grid.
+    fn clip_bands(&mut self) {
+        if self.bands.is_none() || self.pen.clip().is_some() {
+            return;
+        }
+        let d = 1 << self.plot_depth;
+        let size: T2<N, N> = T2(self.tiles.0 * d, self.tiles.1 * d).cast().unwrap();
+        let (lo, hi) = self.pen.transform().apply_rect(T2(N::int(0), N::int(0)), size);
+        self.pen.set_clip(lo, hi);
+    }
+    
+    /// Run over all root cells. Each is a quadtree with cells of size 1 at the plot depth.
+    /// Bands are clipped to the grid if the pen has no clip rectangle.
     pub fn run(&mut self) {
-        let q = {
-            let d = 1 << self.plot_depth;
-            let f = |x, y| (self.func)(T2(x, y));
-            Cell { x: 0, y: 0, d: d, f_tl: f(0, d), f_tr: f(d, d), f_bl: f(0, 0), f_br: f(d, 0) }
+        self.reset();
+        self.clip_bands();
+        for ty in 0 .. self.tiles.1 {
+            for tx in 0 .. self.tiles.0 {
+                let q = self.root(tx, ty);
+                self.create_tree(q, 0);
+            }
+        }
+        self.pen.flush();
+    }
+    
+    /// Like `run`, and also return the contour lines, in grid coordinates.
+    pub fn polylines(&mut self) -> Vec<Polyline<N>> {
+        self.segments = Some(Vec::new());
+        self.run();
+        stitch(self.segments.take().unwrap())
+    }
+    
+    /// Like `run`, with the cells a few levels down (but above the search depth) distributed over `threads` threads.
+    /// Every thread draws into a buffer, and the buffers are drawn in the order of the cells,
+    /// so the result does not depend on the timing of the threads.
+    pub fn run_parallel(&mut self, threads: usize)
+        where F: Sync, B: Sync, N: Send + Sync
+    {
+        assert!(threads > 0);
+        self.reset();
+        self.clip_bands();
+        
+        // the cells above the search depth are split anyway
+        let split = cmp::min(self.search_depth, 3);
+        let mut tasks = Vec::new();
+        for ty in 0 .. self.tiles.1 {
+            for tx in 0 .. self.tiles.0 {
+                let q = self.root(tx, ty);
+                self.collect(q, 0, split, &mut tasks);
+            }
+        }
+        
+        let results = {
+            let tasks = &tasks;
+            let template = self.pen.fork(|_: T2<isize, isize>, _: N| ());
+            let template = &template;
+            let func = Shared(&self.func);
+            let bound = self.bound.as_ref();
+            let levels = &self.levels;
+            let bands = &self.bands;
+            let collect_segments = self.segments.is_some();
+            let (search_depth, plot_depth, tolerance, memoize) = (self.search_depth, self.plot_depth, self.tolerance, self.memoize);
+            
+            crossbeam::scope(|scope| {
+                let handles: Vec<_> = (0 .. threads).map(|t| scope.spawn(move || {
+                    let mut results = Vec::new();
+                    for i in (0 ..).map(|k| t + k * threads).take_while(|&i| i < tasks.len()) {
+                        let mut pixels = Vec::new();
+                        let segments = {
+                            let mut pen = template.fork(|p, v| pixels.push((p, v)));
+                            // the area rasterizer only needs a buffer for the cell, and a pixel around it
+                            if let Some((lo, hi)) = pen.clip() {
+                                let q = tasks[i];
+                                let (a, b) = pen.transform().apply_rect(
+                                    T2(q.x, q.y).cast().unwrap(),
+                                    T2(q.x + q.d, q.y + q.d).cast().unwrap()
+                                );
+                                let one = N::int(1);
+                                let lo = T2(max(lo.0, a.0 - one), max(lo.1, a.1 - one));
+                                let hi = T2(max(lo.0, min(hi.0, b.0 + one)), max(lo.1, min(hi.1, b.1 + one)));
+                                pen.set_clip(lo, hi);
+                            }
+                            let mut c = ContourPlot {
+                                search_depth: search_depth,
+                                plot_depth: plot_depth,
+                                tiles: (1, 1),
+                                levels: levels.clone(),
+                                bands: bands.clone(),
+                                tolerance: tolerance,
+                                pen: pen,
+                                func: func,
+                                bound: bound,
+                                memoize: memoize,
+                                segments: if collect_segments { Some(Vec::new()) } else { None },
+                                cache: RefCell::new(HashMap::new()),
+                                stats: RefCell::new(ContourStats::default())
+                            };
+                            c.create_tree(tasks[i], split);
+                            c.pen.flush();
+                            (c.segments, c.stats())
+                        };
+                        results.push((i, pixels, segments));
+                    }
+                    results
+                })).collect();
+                
+                let mut results: Vec<_> = handles.into_iter().flat_map(|h| h.join()).collect();
+                results.sort_by_key(|r| r.0);
+                results
+            })
         };
-        self.create_tree(q, 0);
+        
+        for (_, pixels, (segments, stats)) in results {
+            // the forked pens applied the weight already
+            for (p, v) in pixels {
+                self.pen.plot_raw(p, v);
+            }
+            {
+                let mut total = self.stats.borrow_mut();
+                total.evaluations += stats.evaluations;
+                total.cache_hits += stats.cache_hits;
+            }
+            if let (Some(all), Some(segments)) = (self.segments.as_mut(), segments) {
+                all.extend(segments);
+            }
+        }
+    }
+    
+    // the cells at depth `split` below q
+    fn collect(&self, q: Cell<N>, depth: u8, split: u8, out: &mut Vec<Cell<N>>) {
+        if depth < split {
+            for q in self.split(q).into_elements() {
+                self.collect(q, depth+1, split, out);
+            }
+        } else {
+            out.push(q);
+        }
     }
     fn create_tree(&mut self, q: Cell<N>, depth: u8) {
         if depth < self.search_depth {
             self.subdivide(q, depth);
+        } else if !q.finite() {
+            // narrow down the bad values, and skip the cells around them
+            if depth < self.plot_depth {
+                self.subdivide(q, depth);
+            }
         } else if self.contour_present(q) {
             if depth < self.plot_depth {
                 self.subdivide(q, depth);
+            } else if self.bands.is_some() {
+                self.fill_bands(q);
             } else {
                 self.plot(q);
             }
+        } else if depth < self.plot_depth && self.may_cross(&q) {
+            self.subdivide(q, depth);
+        } else if self.bands.is_some() {
+            // the whole cell is in one band
+            let band = self.band(q.f_tl);
+            self.fill_band(q, band);
         }
     }
     fn subdivide(&mut self, q: Cell<N>, depth: u8) {
-        for q in q.split(|x, y| (self.func)(T2(x, y))).into_elements() {
-            self.create_tree(q, depth+1);
+        let cells = self.split(q);
+        self.create_trees(cells, depth+1);
+    }
+    // Above the search depth all four siblings are split, so they are split together,
+    // to fill the lanes of samplers like `Lanes<f32x8>`.
+    fn create_trees(&mut self, cells: Quad<N>, depth: u8) {
+        if depth < self.search_depth {
+            for cells in self.split_siblings(cells).into_elements() {
+                self.create_trees(cells, depth+1);
+            }
+        } else {
+            for q in cells.into_elements() {
+                self.create_tree(q, depth);
+            }
+        }
+    }
+    // some corners are below a level, and some are not
+    fn crosses(q: &Cell<N>, level: N) -> bool {
+        let (lo, hi) = q.range();
+        lo < level && level <= hi
+    }
+    // a level is within the bound over the cell
+    fn may_cross(&self, q: &Cell<N>) -> bool {
+        match self.bound {
+            Some(ref bound) => {
+                let x0: N = q.x.cast().unwrap();
+                let y0: N = q.y.cast().unwrap();
+                let d: N = q.d.cast().unwrap();
+                let r = bound.bound(T2(Interval::new(x0, x0 + d), Interval::new(y0, y0 + d)));
+                // an infinite or NaN bound says nothing
+                !r.is_finite() || self.levels.iter().any(|&(c, _)| r.contains(c))
+            },
+            None => false
         }
     }
     fn contour_present(&self, q: Cell<N>) -> bool {
-        let zero = T4::splat(N::int(0));
-        let values = T4(q.f_tl, q.f_tr, q.f_bl, q.f_br);
-        // cheat a bit to compute this a lot more efficient
-        let s = values.lt(zero).map(|b| b as u8);
-        // all false (4*0) or true (4*1) means no contour,
-        // so 1, 2 and 3 have to return true, while 0 and 4 return true.
-        s.into_elements().sum::<u8>() & 3 != 0
+        self.levels.iter().any(|&(c, _)| Self::crosses(&q, c))
     }
     fn plot(&mut self, q: Cell<N>) {
-        let zero = |a: N, b: N| {
-            let x = a / (a - b);
-            if x.ge(N::int(0)) && x.le(N::int(1)) {
-                Some(x)
+        for i in 0 .. self.levels.len() {
+            let (c, w) = self.levels[i];
+            if Self::crosses(&q, c) {
+                self.plot_level(i, q.shift(c), c, w);
+            }
+        }
+    }
+    // Locate the crossing of level c on an edge of q with the Illinois method,
+    // until it moves less than `tolerance`. It stops at a NaN or infinite value,
+    // with the last estimate that had a finite one, or the linear estimate.
+    fn refine(&self, q: &Cell<N>, edge: Edge, c: N, tolerance: N) -> T2<N, N> {
+        let (one, zero) = (N::int(1), N::int(0));
+        let (start, dir, mut fa, mut fb) = edge_values(q, edge);
+        let d: N = q.d.cast().unwrap();
+        let tolerance = tolerance / d;
+
+        let (mut a, mut b) = (zero, one);
+        let linear = fa / (fa - fb);
+        let mut t = linear;
+        let mut finite = None;
+        let mut side = 0;
+        for _ in 0 .. 32 {
+            let ft = self.call(start + dir * t) - c;
+            if ft - ft != zero {
+                t = finite.unwrap_or(linear);
+                break;
+            }
+            finite = Some(t);
+            if ft == zero {
+                break;
+            }
+            if (ft < zero) == (fb < zero) {
+                b = t;
+                fb = ft;
+                if side == -1 {
+                    fa = fa / N::int(2);
+                }
+                side = -1;
             } else {
-                None
+                a = t;
+                fa = ft;
+                if side == 1 {
+                    fb = fb / N::int(2);
+                }
+                side = 1;
             }
-        };
+            let next = (a * fb - b * fa) / (fb - fa);
+            let done = (next - t).abs() < tolerance;
+            t = next;
+            if done {
+                break;
+            }
+        }
+        if t - t != zero {
+            t = finite.unwrap_or(linear);
+        }
+        start + dir * t
+    }
+    
+    // index of the band v lies in
+    fn band(&self, v: N) -> usize {
+        self.levels.iter().take_while(|&&(c, _)| c <= v).count()
+    }
+    fn fill_band(&mut self, q: Cell<N>, band: usize) {
+        let x0: N = q.x.cast().unwrap();
+        let y0: N = q.y.cast().unwrap();
+        let d: N = q.d.cast().unwrap();
+        let value = self.bands.as_ref().unwrap()[band];
+        
+        self.pen.set_weight(value);
+        self.pen.fill(&[[T2(x0, y0), T2(x0 + d, y0), T2(x0 + d, y0 + d), T2(x0, y0 + d)]]);
+    }
+    // marching squares for bands: clip the cell to each band it touches
+    fn fill_bands(&mut self, q: Cell<N>) {
+        let x0: N = q.x.cast().unwrap();
+        let y0: N = q.y.cast().unwrap();
+        let d: N = q.d.cast().unwrap();
+        let cell = [
+            (T2(x0,     y0    ), q.f_bl),
+            (T2(x0 + d, y0    ), q.f_br),
+            (T2(x0 + d, y0 + d), q.f_tr),
+            (T2(x0,     y0 + d), q.f_tl)
+        ];
+        
+        // no band edges through poles
+        let edges = [(q.x, q.y, true), (q.x, q.y + q.d, true), (q.x, q.y, false), (q.x + q.d, q.y, false)];
+        for &(c, _) in &self.levels {
+            let shifted = q.shift(c);
+            let crossed: Vec<Edge> = edges.iter().cloned().filter(|&e| {
+                let (_, _, fa, fb) = edge_values(&shifted, e);
+                (fa < N::int(0)) != (fb < N::int(0))
+            }).collect();
+            if self.pole(&shifted, &crossed, c) {
+                return;
+            }
+        }
+        
+        let (lo, hi) = q.range();
+        for band in self.band(lo) ... self.band(hi) {
+            let mut poly = cell.to_vec();
+            if band > 0 {
+                poly = clip_polygon(&poly, self.levels[band - 1].0, false);
+            }
+            if band < self.levels.len() {
+                poly = clip_polygon(&poly, self.levels[band].0, true);
+            }
+            if poly.len() >= 3 {
+                let points: Vec<T2<N, N>> = poly.iter().map(|&(p, _)| p).collect();
+                self.pen.set_weight(self.bands.as_ref().unwrap()[band]);
+                self.pen.fill(&[points]);
+            }
+        }
+    }
+    // q has c subtracted already
+    // The sign change along one of `edges` of q is because of a pole, and not a zero.
+    // Halving the edge moves the values away from zero at a pole, and towards it at a zero.
+    // Only edges that may be poles (see `Cell::jumps`) are checked, with one batch of evaluations.
+    fn pole(&self, q: &Cell<N>, edges: &[Edge], c: N) -> bool {
+        let zero = N::int(0);
+        let half = N::int(1) / N::int(2);
+        let mut points = [T2(zero, zero); 4];
+        let mut ends = [(zero, zero); 4];
+        let mut n = 0;
+        for &edge in edges {
+            let (start, dir, fa, fb) = edge_values(q, edge);
+            if q.jumps(fa, fb) {
+                points[n] = start + dir * half;
+                ends[n] = (fa, fb);
+                n += 1;
+            }
+        }
+        if n == 0 {
+            return false;
+        }
+        
+        let mut middle = [zero; 4];
+        self.stats.borrow_mut().evaluations += n;
+        self.func.sample_batch(&points[.. n], &mut middle[.. n]);
+        
+        let smallest = |a: N, b: N| { let (a, b) = (a.abs(), b.abs()); if a < b { a } else { b } };
+        ends[.. n].iter().zip(middle[.. n].iter()).any(|(&(fa, fb), &fm)| {
+            let fm = fm - c;
+            if fm - fm != zero {
+                return true;
+            }
+            let (ga, gb) = if (fm < zero) == (fa < zero) { (fm, fb) } else { (fa, fm) };
+            smallest(ga, gb) > smallest(fa, fb)
+        })
+    }
+    
+    fn plot_level(&mut self, level: usize, q: Cell<N>, c: N, w: N) {
+        for &(a, b) in cell_segments(&q).iter().filter_map(|s| s.as_ref()) {
+            if self.pole(&q, &[a.0, b.0], c) {
+                continue;
+            }
+            let (a, b) = match self.tolerance {
+                Some(tolerance) => ((a.0, self.refine(&q, a.0, c, tolerance)), (b.0, self.refine(&q, b.0, c, tolerance))),
+                None => (a, b)
+            };
+            self.pen.move_to(a.1);
+            self.pen.line_to_weighted(b.1, w);
+            if let Some(ref mut segments) = self.segments {
+                segments.push((level, a, b));
+            }
+        }
+    }
+}
+
+// start, direction (with the length of the cell) and the values at both ends of an edge of q
+fn edge_values<N>(q: &Cell<N>, edge: Edge) -> (T2<N, N>, T2<N, N>, N, N)
+    where N: Real + Debug, usize: Cast<N>
+{
+    let (x, y, horizontal) = edge;
+    let (one, zero) = (N::int(1), N::int(0));
+    let (dir, fa, fb) = match (horizontal, x == q.x && y == q.y) {
+        (true,  true)  => (T2(one, zero), q.f_bl, q.f_br), // bottom
+        (true,  false) => (T2(one, zero), q.f_tl, q.f_tr), // top
+        (false, true)  => (T2(zero, one), q.f_bl, q.f_tl), // left
+        (false, false) => (T2(zero, one), q.f_br, q.f_tr)  // right
+    };
+    let start: T2<N, N> = T2(x, y).cast().unwrap();
+    let d: N = q.d.cast().unwrap();
+    (start, dir * d, fa, fb)
+}
+
+// Marching squares on a single cell: the segments of the zero contour, in grid coordinates.
+// Corners with a negative value are below the contour. If all four edges are crossed (a saddle),
+// the value of the bilinear interpolation at the saddle point decides which corners are connected.
+fn cell_segments<N>(q: &Cell<N>) -> [Option<((Edge, T2<N, N>), (Edge, T2<N, N>))>; 2]
+    where N: Real + PartialOrd + Debug, usize: Cast<N>
+{
+    let zero = N::int(0);
+    let below = |v: N| v < zero;
+    let crossing = |a: N, b: N| if below(a) != below(b) {
+        Some(a / (a - b))
+    } else {
+        None
+    };
+
+    let x0: N = q.x.cast().unwrap();
+    let y0: N = q.y.cast().unwrap();
+    let d: N = q.d.cast().unwrap();
+
+    let (x, y) = (q.x, q.y);
+    let top    = crossing(q.f_tl, q.f_tr).map(|a| ((x,     y+q.d, true ), T2(x0 + a * d, y0 + d    )));
+    let bottom = crossing(q.f_bl, q.f_br).map(|a| ((x,     y,     true ), T2(x0 + a * d, y0        )));
+    let left   = crossing(q.f_bl, q.f_tl).map(|a| ((x,     y,     false), T2(x0,         y0 + a * d)));
+    let right  = crossing(q.f_br, q.f_tr).map(|a| ((x+q.d, y,     false), T2(x0 + d,     y0 + a * d)));
+
+    match (top, bottom, left, right) {
+        (Some(t), Some(b), Some(l), Some(r)) => {
+            // the diagonals have the same sign, so the denominator can't be zero
+            let center = (q.f_tl * q.f_br - q.f_tr * q.f_bl) / (q.f_tl + q.f_br - q.f_tr - q.f_bl);
+            if below(center) == below(q.f_tl) {
+                // tl and br are connected through the center, cut off tr and bl
+                [Some((t, r)), Some((b, l))]
+            } else {
+                [Some((t, l)), Some((b, r))]
+            }
+        },
+        _ => {
+            let mut iter = T4(top, bottom, left, right).into_elements().filter_map(|x| x);
+            match (iter.next(), iter.next()) {
+                (Some(p0), Some(p1)) => [Some((p0, p1)), None],
+                _ => [None, None]
+            }
+        }
+    }
+}
+
+// Sutherland-Hodgman: the part of the polygon where the (linearly interpolated) value
+// is at least c, or below c if `below` is set.
+fn clip_polygon<N>(poly: &[(T2<N, N>, N)], c: N, below: bool) -> Vec<(T2<N, N>, N)>
+    where N: Real + PartialOrd
+{
+    let inside = |v: N| if below { v < c } else { v >= c };
+    let mut out = Vec::with_capacity(poly.len() + 2);
+    
+    if let Some(&last) = poly.last() {
+        let mut a = last;
+        for &b in poly {
+            let (pa, va) = a;
+            let (pb, vb) = b;
+            if inside(va) != inside(vb) {
+                let t = (c - va) / (vb - va);
+                out.push((pa + (pb - pa) * t, c));
+            }
+            if inside(vb) {
+                out.push(b);
+            }
+            a = b;
+        }
+    }
+    out
+}
+
+// Join the segments that end on the same edge into lines.
+fn stitch<N: Copy>(segments: Vec<Segment<N>>) -> Vec<Polyline<N>> {
+    // segments ending on each edge, at most two
+    let mut ends: HashMap<(usize, Edge), Vec<usize>> = HashMap::new();
+    for (i, &(level, a, b)) in segments.iter().enumerate() {
+        ends.entry((level, a.0)).or_insert_with(Vec::new).push(i);
+        ends.entry((level, b.0)).or_insert_with(Vec::new).push(i);
+    }
+    
+    let mut used = vec![false; segments.len()];
+    
+    // follow the segments from `edge` on, until the line ends or reaches `stop`
+    let walk = |used: &mut Vec<bool>, level: usize, mut edge: Edge, stop: Edge, points: &mut Vec<T2<N, N>>| -> bool {
+        loop {
+            let next = ends[&(level, edge)].iter().cloned().find(|&j| !used[j]);
+            match next {
+                Some(j) => {
+                    used[j] = true;
+                    let (_, a, b) = segments[j];
+                    let (e, p) = if a.0 == edge { b } else { a };
+                    if e == stop {
+                        return true;
+                    }
+                    points.push(p);
+                    edge = e;
+                },
+                None => return false
+            }
+        }
+    };
+    
+    let mut lines = Vec::new();
+    for i in 0 .. segments.len() {
+        if used[i] {
+            continue;
+        }
+        used[i] = true;
+        let (level, a, b) = segments[i];
+        
+        let mut points = vec![a.1, b.1];
+        let closed = walk(&mut used, level, b.0, a.0, &mut points);
+        if !closed {
+            // the start is not closed either, so continue from there
+            let mut head = Vec::new();
+            walk(&mut used, level, a.0, b.0, &mut head);
+            head.reverse();
+            head.extend(points);
+            points = head;
+        }
+        lines.push(Polyline { points: points, closed: closed, level: level });
+    }
+    lines
+}
+
+#[cfg(test)]
+fn saddle_cell(x: usize, y: usize, d: usize, c: f64) -> Cell<f64> {
+    // (x - 2) (y - 2) + c, with the saddle at (2, 2)
+    let f = |x: usize, y: usize| (x as f64 - 2.) * (y as f64 - 2.) + c;
+    Cell { x: x, y: y, d: d, f_tl: f(x, y+d), f_tr: f(x+d, y+d), f_bl: f(x, y), f_br: f(x+d, y), spread: None }
+}
+
+// circle of radius r around center
+#[cfg(test)]
+fn circle(center: T2<f64, f64>, r: f64) -> impl Fn(T2<f64, f64>) -> f64 {
+    move |p| {
+        let T2(x, y) = p - center;
+        x * x + y * y - r * r
+    }
+}
+
+#[cfg(test)]
+fn no_pixels(_: T2<isize, isize>, _: f64) {}
+
+// a plot of func that draws nothing
+#[cfg(test)]
+fn blind<F: Sampler<f64>>(func: F, plot_depth: u8) -> ContourPlot<F, fn(T2<isize, isize>, f64), f64> {
+    ContourPlot::new(func, Pen::new(no_pixels as fn(T2<isize, isize>, f64)), plot_depth)
+}
+
+#[test]
+fn test_saddle_segments() {
+    // above the saddle: the contour bends around the bottom-right and top-left corners
+    let s = cell_segments(&saddle_cell(1, 1, 2, 0.1));
+    let ((_, T2(x0, y0)), (_, T2(x1, y1))) = s[0].expect("two segments");
+    let ((_, T2(x2, y2)), (_, T2(x3, y3))) = s[1].expect("two segments");
+    assert!(x0 < 2. && y0 == 3. && x1 == 1. && y1 > 2.); // top - left
+    assert!(x2 > 2. && y2 == 1. && x3 == 3. && y3 < 2.); // bottom - right
+
+    // below the saddle: around the top-right and bottom-left corners
+    let s = cell_segments(&saddle_cell(1, 1, 2, -0.1));
+    let ((_, T2(x0, y0)), (_, T2(x1, y1))) = s[0].expect("two segments");
+    let ((_, T2(x2, y2)), (_, T2(x3, y3))) = s[1].expect("two segments");
+    assert!(x0 > 2. && y0 == 3. && x1 == 3. && y1 > 2.); // top - right
+    assert!(x2 < 2. && y2 == 1. && x3 == 1. && y3 < 2.); // bottom - left
+}
+
+#[test]
+fn test_saddle_cells() {
+    // unit cells around the saddle only see one branch of the hyperbola each
+    for &c in &[0.1, -0.1] {
+        let mut count = 0;
+        for y in 0 .. 4 {
+            for x in 0 .. 4 {
+                count += cell_segments(&saddle_cell(x, y, 1, c)).iter().filter(|s| s.is_some()).count();
+            }
+        }
+        // each of the two branches crosses three cells
+        assert_eq!(count, 6);
+    }
+}
 
-        let x0 = q.x.cast().unwrap();
-        let y0 = q.y.cast().unwrap();
-        let d = q.d.cast().unwrap();
+#[test]
+fn test_polylines() {
+    let lines = blind(circle(T2(16., 16.), 10.), 5).polylines();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].closed);
 
-        let top    = zero(q.f_tl, q.f_tr).map(|a| T2(x0 + a * d, y0 + d    ));
-        let bottom = zero(q.f_bl, q.f_br).map(|a| T2(x0 + a * d, y0        ));
-        let left   = zero(q.f_bl, q.f_tl).map(|a| T2(x0,         y0 + a * d));
-        let right  = zero(q.f_br, q.f_tr).map(|a| T2(x0 + d,     y0 + a * d));
+    let pi = ::std::f64::consts::PI;
+    assert!((lines[0].length() / (20. * pi) - 1.).abs() < 0.01);
+    assert!((lines[0].area().abs() / (100. * pi) - 1.).abs() < 0.01);
+}
+
+#[test]
+fn test_refine() {
+    // a coarse circle, with the points on the circle nevertheless
+    let mut c = blind(|p: T2<f64, f64>| {
+        let T2(x, y) = p - T2(4., 4.);
+        (x * x + y * y).sqrt() - 3.
+    }, 3);
+    c.tolerance = Some(1e-9);
+    for line in c.polylines() {
+        for p in line.points {
+            let T2(x, y) = p - T2(4., 4.);
+            assert!(((x * x + y * y).sqrt() - 3.).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_refine_nan() {
+    // NaN around the crossing, but not at the grid points: the linear estimate stays
+    let mut c = blind(|T2(x, _): T2<f64, f64>| if x > 2.25 && x < 2.35 { ::std::f64::NAN } else { x - 2.3 }, 3);
+    c.tolerance = Some(1e-9);
+    let lines = c.polylines();
+    assert!(!lines.is_empty());
+    for line in lines {
+        assert!(line.points.iter().all(|p| (p.0 - 2.3).abs() < 1e-9 && p.1 - p.1 == 0.));
+    }
+}
+
+#[test]
+fn test_bands() {
+    // without a clip rectangle on the pen, the 4 x 4 grid is filled:
+    // 1 left of x = 2.5, and 2 right of it
+    let mut total = 0.;
+    {
+        let mut c = ContourPlot::new(|T2(x, _): T2<f64, f64>| x - 2.5, Pen::new(|_, v: f64| total += v), 2);
+        c.bands = Some(vec![1., 2.]);
+        c.run();
+    }
+    assert!((total - (2.5 * 4. * 1. + 1.5 * 4. * 2.)).abs() < 1e-9, "{}", total);
+}
+
+#[test]
+fn test_bound() {
+    // a small circle, between the corners of the root cell
+    let new = || {
+        let mut c = blind(circle(T2(5.2, 5.3), 0.5), 4);
+        c.search_depth = 0;
+        c
+    };
+    assert_eq!(new().polylines().len(), 0);
+
+    let bound = |p: T2<Interval<f64>, Interval<f64>>| {
+        let T2(x, y) = p;
+        (x - 5.2).sqr() + (y - 5.3).sqr() - 0.25
+    };
+    let lines = new().with_bound(Some(bound)).polylines();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].closed);
+
+    // 0 * (1 / [-a, b]) is not NaN, and the cells are not culled
+    let zero = |p: T2<Interval<f64>, Interval<f64>>| {
+        let T2(x, _) = p;
+        Interval::point(0.) * (Interval::point(1.) / (x - 5.))
+    };
+    assert_eq!(new().with_bound(Some(zero)).polylines().len(), 1);
+}
+
+#[test]
+fn test_parallel() {
+    let circle = circle(T2(30., 20.), 14.);
+    let run = |threads: usize| {
+        let mut pixels = Vec::new();
+        {
+            let mut c = ContourPlot::new(&circle, Pen::new(|p: T2<isize, isize>, v: f64| pixels.push((p, v))), 5);
+            c.tiles = (2, 2);
+            c.pen.set_weight(0.5);
+            if threads > 1 {
+                c.run_parallel(threads);
+            } else {
+                c.run();
+            }
+        }
+        pixels
+    };
+    // same pixels, in the same order
+    assert_eq!(run(1), run(3));
+}
+
+#[test]
+fn test_parallel_area() {
+    // with area rasterization every task has a buffer of its own, so compare the sums per pixel
+    let circle = circle(T2(30., 20.), 14.);
+    let run = |threads: usize, bands: bool| {
+        let mut pixels = HashMap::new();
+        {
+            let pen = Pen::new(|p: T2<isize, isize>, v: f64| *pixels.entry((p.0, p.1)).or_insert(0.) += v);
+            let mut c = ContourPlot::new(&circle, pen, 5);
+            c.tiles = (2, 2);
+            c.pen.set_clip(T2(-1., -1.), T2(64., 64.));
+            c.pen.set_raster(::pen::Raster::Area(1.5));
+            if bands {
+                c.bands = Some(vec![0.25, 0.75]);
+            }
+            if threads > 1 {
+                c.run_parallel(threads);
+            } else {
+                c.run();
+            }
+        }
+        pixels
+    };
+    for &bands in &[false, true] {
+        let (serial, parallel) = (run(1, bands), run(3, bands));
+        assert!(!serial.is_empty());
+        for (p, &v) in serial.iter().chain(parallel.iter()) {
+            let (a, b) = (serial.get(p).cloned().unwrap_or(0.), parallel.get(p).cloned().unwrap_or(0.));
+            assert!((a - b).abs() < 1e-9, "pixel {:?}: {} and {}", p, a, b);
+            assert!(v >= 0.);
+        }
+    }
+}
+
+#[test]
+fn test_memoize() {
+    let run = |memoize: bool| {
+        let mut c = blind(circle(T2(16., 16.), 10.), 5);
+        c.memoize = memoize;
+        let lines = c.polylines();
+        (lines[0].points.clone(), c.stats())
+    };
+    let (plain, plain_stats) = run(false);
+    let (memoized, stats) = run(true);
+    assert_eq!(plain, memoized);
+    assert_eq!(plain_stats.cache_hits, 0);
+    assert_eq!(stats.evaluations + stats.cache_hits, plain_stats.evaluations);
+    assert!(stats.evaluations < plain_stats.evaluations);
+}
+
+#[test]
+fn test_poles() {
+    let lines = |f: fn(T2<f64, f64>) -> f64| {
+        blind(f, 4).polylines()
+    };
+    
+    // the sign changes at x = 7.3, but there is no zero
+    assert_eq!(lines(|T2(x, _)| 1. / (x - 7.3)).len(), 0);
+    
+    // NaN left of x = 3, and a zero at x = 7
+    let ls = lines(|T2(x, _)| (x - 3.).sqrt() - 2.);
+    assert!(!ls.is_empty());
+    for l in ls {
+        assert!(l.points.iter().all(|p| (p.0 - 7.).abs() < 1e-9));
+    }
+    
+    // no band edge at the pole either: the cell around it is left out,
+    // and the pixels 7 and 8 on both sides of it are half covered by the cells next to it
+    let mut at_pole = 0.;
+    {
+        let f = |T2(x, _): T2<f64, f64>| 1. / (x - 7.3);
+        let pen = Pen::new(|p: T2<isize, isize>, v: f64| if p.1 == 8 && (p.0 == 7 || p.0 == 8) { at_pole += v });
+        let mut c = ContourPlot::new(f, pen, 4);
+        c.pen.set_clip(T2(0., 0.), T2(16., 16.));
+        c.bands = Some(vec![1., 2.]);
+        c.run();
+    }
+    assert!((at_pole - (0.5 * 1. + 0.5 * 2.)).abs() < 1e-9, "{}", at_pole);
+    
+    // smooth functions are not probed
+    let mut c = blind(|T2(x, _): T2<f64, f64>| x - 7.3, 4);
+    c.memoize = true;
+    c.run();
+    assert_eq!(c.stats().evaluations, c.cache.borrow().len());
+}
 
-        let mut iter = T4(top, bottom, left, right).into_elements().filter_map(|x| x).map(|x| x.cast().unwrap().into());
-        match (iter.next(), iter.next()) {
-            (Some(p0), Some(p1)) => self.pen.line(p0, p1),
-            _ => ()
+#[test]
+fn test_eval_batches() {
+    // more points than fit in a batch, with repeats
+    let points: Vec<(usize, usize)> = (0 .. 45).map(|i| (i % 7, i / 7 % 4)).collect();
+    for &memoize in &[false, true] {
+        let mut c = blind(|T2(x, y): T2<f64, f64>| x * 100. + y, 4);
+        c.memoize = memoize;
+        let mut out = vec![0.; points.len()];
+        c.eval(&points, &mut out);
+        for (&(x, y), &v) in points.iter().zip(out.iter()) {
+            assert_eq!(v, x as f64 * 100. + y as f64);
         }
+        
+        // each of the 28 different points is evaluated once, with memoize
+        let stats = c.stats();
+        assert_eq!(stats.evaluations + stats.cache_hits, points.len());
+        assert_eq!(stats.evaluations, if memoize { 28 } else { points.len() });
     }
 }
@@ -0,0 +1,202 @@
+use std::ops::{Add, Sub, Mul, Div, Neg, AddAssign, SubAssign, MulAssign, DivAssign};
+use rand::Rng;
+use tuple::T2;
+use math::real::Real;
+
+/// A value together with its gradient with respect to the point `(x, y)`.
+///
+/// Arithmetic on duals applies the chain rule (forward mode differentiation),
+/// so a function written for any `Real` also returns its gradient when called with `Dual::variables(p)`.
+#[derive(Copy, Clone, Debug)]
+pub struct Dual<V> {
+    pub value: V,
+    pub grad: T2<V, V>
+}
+
+impl<V: Real> Dual<V> {
+    /// A value that does not depend on the point.
+    pub fn constant(value: V) -> Dual<V> {
+        Dual { value: value, grad: T2(V::int(0), V::int(0)) }
+    }
+
+    /// `x` and `y` of the point `p`.
+    pub fn variables(p: T2<V, V>) -> T2<Dual<V>, Dual<V>> {
+        let (zero, one) = (V::int(0), V::int(1));
+        T2(
+            Dual { value: p.0, grad: T2(one, zero) },
+            Dual { value: p.1, grad: T2(zero, one) }
+        )
+    }
+
+    /// `grad / |grad|²`, so `p - value * grad_inv()` is a Newton step towards the zero set.
+    pub fn grad_inv(&self) -> T2<V, V> {
+        let T2(x, y) = self.grad;
+        let l = (x * x + y * y).inv();
+        T2(x * l, y * l)
+    }
+
+    // f(self), where f has the derivative `d` at self.value
+    #[inline(always)]
+    fn chain(self, value: V, d: V) -> Dual<V> {
+        Dual { value: value, grad: T2(self.grad.0 * d, self.grad.1 * d) }
+    }
+}
+
+impl<V: Real> Add for Dual<V> {
+    type Output = Dual<V>;
+    fn add(self, rhs: Dual<V>) -> Dual<V> {
+        Dual { value: self.value + rhs.value, grad: T2(self.grad.0 + rhs.grad.0, self.grad.1 + rhs.grad.1) }
+    }
+}
+impl<V: Real> Sub for Dual<V> {
+    type Output = Dual<V>;
+    fn sub(self, rhs: Dual<V>) -> Dual<V> {
+        Dual { value: self.value - rhs.value, grad: T2(self.grad.0 - rhs.grad.0, self.grad.1 - rhs.grad.1) }
+    }
+}
+impl<V: Real> Mul for Dual<V> {
+    type Output = Dual<V>;
+    fn mul(self, rhs: Dual<V>) -> Dual<V> {
+        Dual {
+            value: self.value * rhs.value,
+            grad: T2(
+                self.grad.0 * rhs.value + self.value * rhs.grad.0,
+                self.grad.1 * rhs.value + self.value * rhs.grad.1
+            )
+        }
+    }
+}
+impl<V: Real> Div for Dual<V> {
+    type Output = Dual<V>;
+    fn div(self, rhs: Dual<V>) -> Dual<V> {
+        self * rhs.inv()
+    }
+}
+impl<V: Real> Neg for Dual<V> {
+    type Output = Dual<V>;
+    fn neg(self) -> Dual<V> {
+        Dual { value: -self.value, grad: T2(-self.grad.0, -self.grad.1) }
+    }
+}
+
+impl<V: Real> AddAssign for Dual<V> {
+    fn add_assign(&mut self, rhs: Dual<V>) {
+        *self = *self + rhs;
+    }
+}
+impl<V: Real> SubAssign for Dual<V> {
+    fn sub_assign(&mut self, rhs: Dual<V>) {
+        *self = *self - rhs;
+    }
+}
+impl<V: Real> MulAssign for Dual<V> {
+    fn mul_assign(&mut self, rhs: Dual<V>) {
+        *self = *self * rhs;
+    }
+}
+impl<V: Real> DivAssign for Dual<V> {
+    fn div_assign(&mut self, rhs: Dual<V>) {
+        *self = *self / rhs;
+    }
+}
+
+impl<V: Real> Real for Dual<V> where V::Bool: Copy {
+    const PRECISION: usize = V::PRECISION;
+    type Bool = V::Bool;
+    type Scalar = V::Scalar;
+    type Iterator = V::Iterator;
+
+    // the values only; the gradient is lost
+    fn values(self) -> V::Iterator {
+        self.value.values()
+    }
+    fn int(v: i16) -> Self {
+        Dual::constant(V::int(v))
+    }
+    fn frac(nom: i16, denom: u16) -> Self {
+        Dual::constant(V::frac(nom, denom))
+    }
+    fn splat(s: V::Scalar) -> Self {
+        Dual::constant(V::splat(s))
+    }
+    fn uniform01<R: Rng>(rng: &mut R) -> Self {
+        Dual::constant(V::uniform01(rng))
+    }
+
+    fn inv(self) -> Self {
+        let inv = self.value.inv();
+        self.chain(inv, -(inv * inv))
+    }
+    fn sqrt(self) -> Self {
+        let sqrt = self.value.sqrt();
+        self.chain(sqrt, (sqrt + sqrt).inv())
+    }
+    fn exp(self) -> Self {
+        let exp = self.value.exp();
+        self.chain(exp, exp)
+    }
+    fn log(self) -> Self {
+        self.chain(self.value.log(), self.value.inv())
+    }
+    fn sin(self) -> Self {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+    fn cos(self) -> Self {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+    fn abs(self) -> Self {
+        let zero = V::int(0);
+        self.select(-self, zero.le(self.value))
+    }
+    fn mul_add(self, b: Self, c: Self) -> Self {
+        self * b + c
+    }
+
+    // piecewise constant
+    fn floor(self) -> Self {
+        Dual::constant(self.value.floor())
+    }
+    fn ceil(self) -> Self {
+        Dual::constant(self.value.ceil())
+    }
+
+    fn max(self, other: Self) -> Self {
+        self.select(other, other.value.le(self.value))
+    }
+    fn min(self, other: Self) -> Self {
+        self.select(other, self.value.le(other.value))
+    }
+
+    // comparisons and selection look at the values
+    fn lt(self, rhs: Self) -> V::Bool {
+        self.value.lt(rhs.value)
+    }
+    fn le(self, rhs: Self) -> V::Bool {
+        self.value.le(rhs.value)
+    }
+    fn gt(self, rhs: Self) -> V::Bool {
+        self.value.gt(rhs.value)
+    }
+    fn ge(self, rhs: Self) -> V::Bool {
+        self.value.ge(rhs.value)
+    }
+    fn eq(self, rhs: Self) -> V::Bool {
+        self.value.eq(rhs.value)
+    }
+    fn select(self, other: Self, cond: V::Bool) -> Self {
+        Dual {
+            value: self.value.select(other.value, cond),
+            grad: T2(self.grad.0.select(other.grad.0, cond), self.grad.1.select(other.grad.1, cond))
+        }
+    }
+}
+
+#[test]
+fn test_dual_gradient() {
+    // f(x, y) = x y + sin(x) / y
+    let T2(x, y) = Dual::variables(T2(1.0f64, 2.0));
+    let f = x * y + x.sin() / y;
+    assert!((f.value - (2.0 + 1.0f64.sin() / 2.0)).abs() < 1e-12);
+    assert!((f.grad.0 - (2.0 + 1.0f64.cos() / 2.0)).abs() < 1e-12);
+    assert!((f.grad.1 - (1.0 - 1.0f64.sin() / 4.0)).abs() < 1e-12);
+}
@@ -0,0 +1,312 @@
+use std::ops::{Add, Sub, Mul, Div, Neg, AddAssign, SubAssign, MulAssign, DivAssign};
+use rand::Rng;
+use math::real::Real;
+use util::{max, min};
+
+/// Closed interval `lo ..= hi`.
+///
+/// The result of an operation on intervals contains the results of the operation
+/// for all values in them, so a function written in terms of these operations
+/// bounds the values of the function over a whole region.
+///
+/// `Interval` is a `Real`, so a function generic over `Real` also gives its own bound.
+/// Comparisons are only true if they hold for all values in the intervals.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interval<N> {
+    pub lo: N,
+    pub hi: N
+}
+
+// a * b, with 0 * ±inf = 0, as the zero is exact and the infinity is only a bound
+#[inline(always)]
+fn mul<N: Real + PartialOrd>(a: N, b: N) -> N {
+    let zero = N::int(0);
+    if a == zero || b == zero { zero } else { a * b }
+}
+
+impl<N: Real + PartialOrd> Interval<N> {
+    pub fn new(lo: N, hi: N) -> Interval<N> {
+        Interval { lo: lo, hi: hi }
+    }
+
+    /// The interval containing only `x`.
+    pub fn point(x: N) -> Interval<N> {
+        Interval { lo: x, hi: x }
+    }
+
+    pub fn contains(&self, x: N) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn width(&self) -> N {
+        self.hi - self.lo
+    }
+
+    /// Smallest interval containing both.
+    pub fn hull(self, other: Interval<N>) -> Interval<N> {
+        Interval::new(min(self.lo, other.lo), max(self.hi, other.hi))
+    }
+
+    pub fn min(self, other: Interval<N>) -> Interval<N> {
+        Interval::new(min(self.lo, other.lo), min(self.hi, other.hi))
+    }
+
+    pub fn max(self, other: Interval<N>) -> Interval<N> {
+        Interval::new(max(self.lo, other.lo), max(self.hi, other.hi))
+    }
+
+    pub fn abs(self) -> Interval<N> {
+        let zero = N::int(0);
+        if self.lo >= zero {
+            self
+        } else if self.hi <= zero {
+            -self
+        } else {
+            Interval::new(zero, max(zero - self.lo, self.hi))
+        }
+    }
+
+    /// `x * x`, which is tighter than the product, as both factors are the same value.
+    pub fn sqr(self) -> Interval<N> {
+        let a = self.abs();
+        Interval::new(a.lo * a.lo, a.hi * a.hi)
+    }
+
+    /// Square root of the non-negative part.
+    pub fn sqrt(self) -> Interval<N> {
+        let zero = N::int(0);
+        Interval::new(max(self.lo, zero).sqrt(), max(self.hi, zero).sqrt())
+    }
+
+    /// Neither end is infinite or NaN.
+    pub fn is_finite(&self) -> bool {
+        let zero = N::int(0);
+        self.lo - self.lo == zero && self.hi - self.hi == zero
+    }
+
+    // f over the interval, for an increasing f
+    fn increasing<F: Fn(N) -> N>(self, f: F) -> Interval<N> {
+        Interval::new(f(self.lo), f(self.hi))
+    }
+
+    // f over the interval, for f = sin or cos with the derivative df: the values at the ends,
+    // and ±1 where df changes its sign. No approximation of pi is involved,
+    // so the ends are exact and no peak is missed.
+    fn wave<F: Fn(N) -> N, D: Fn(N) -> N>(self, f: F, df: D) -> Interval<N> {
+        let (zero, one) = (N::int(0), N::int(1));
+        if !(self.hi - self.lo < N::int(6)) {
+            return Interval::new(-one, one);
+        }
+
+        // the halves are shorter than pi, so df changes its sign at most once in each
+        let piece = |a: N, b: N| {
+            let (fa, fb) = (f(a), f(b));
+            let mut r = Interval::new(min(fa, fb), max(fa, fb));
+            let (da, db) = (df(a), df(b));
+            if da >= zero && db <= zero {
+                r = r.hull(Interval::point(one));
+            }
+            if da <= zero && db >= zero {
+                r = r.hull(Interval::point(-one));
+            }
+            r
+        };
+        let mid = (self.lo + self.hi) / N::int(2);
+        piece(self.lo, mid).hull(piece(mid, self.hi))
+    }
+}
+
+impl<N: Real<Bool=bool> + PartialOrd> Real for Interval<N> {
+    const PRECISION: usize = N::PRECISION;
+    type Bool = bool;
+    type Scalar = N::Scalar;
+    type Iterator = N::Iterator;
+
+    // the lower ends
+    fn values(self) -> N::Iterator {
+        self.lo.values()
+    }
+    fn int(v: i16) -> Self {
+        Interval::point(N::int(v))
+    }
+    fn frac(nom: i16, denom: u16) -> Self {
+        Interval::point(N::frac(nom, denom))
+    }
+    fn splat(s: N::Scalar) -> Self {
+        Interval::point(N::splat(s))
+    }
+    fn uniform01<R: Rng>(rng: &mut R) -> Self {
+        Interval::point(N::uniform01(rng))
+    }
+
+    fn inv(self) -> Self {
+        Interval::point(N::int(1)) / self
+    }
+    fn sqrt(self) -> Self {
+        Interval::sqrt(self)
+    }
+    fn exp(self) -> Self {
+        self.increasing(N::exp)
+    }
+    // of the non-negative part, like `sqrt`
+    fn log(self) -> Self {
+        let zero = N::int(0);
+        Interval::new(max(self.lo, zero), max(self.hi, zero)).increasing(N::log)
+    }
+    fn sin(self) -> Self {
+        self.wave(N::sin, N::cos)
+    }
+    fn cos(self) -> Self {
+        self.wave(N::cos, |x: N| -x.sin())
+    }
+    fn abs(self) -> Self {
+        Interval::abs(self)
+    }
+    fn mul_add(self, b: Self, c: Self) -> Self {
+        self * b + c
+    }
+    fn floor(self) -> Self {
+        self.increasing(N::floor)
+    }
+    fn ceil(self) -> Self {
+        self.increasing(N::ceil)
+    }
+    fn max(self, other: Self) -> Self {
+        Interval::max(self, other)
+    }
+    fn min(self, other: Self) -> Self {
+        Interval::min(self, other)
+    }
+
+    fn lt(self, rhs: Self) -> bool {
+        self.hi < rhs.lo
+    }
+    fn le(self, rhs: Self) -> bool {
+        self.hi <= rhs.lo
+    }
+    fn gt(self, rhs: Self) -> bool {
+        self.lo > rhs.hi
+    }
+    fn ge(self, rhs: Self) -> bool {
+        self.lo >= rhs.hi
+    }
+    fn eq(self, rhs: Self) -> bool {
+        self.lo == self.hi && self == rhs
+    }
+    fn select(self, other: Self, cond: bool) -> Self {
+        if cond { self } else { other }
+    }
+}
+
+impl<N: Real + PartialOrd> Add for Interval<N> {
+    type Output = Interval<N>;
+    fn add(self, rhs: Interval<N>) -> Interval<N> {
+        Interval::new(self.lo + rhs.lo, self.hi + rhs.hi)
+    }
+}
+impl<N: Real + PartialOrd> Sub for Interval<N> {
+    type Output = Interval<N>;
+    fn sub(self, rhs: Interval<N>) -> Interval<N> {
+        Interval::new(self.lo - rhs.hi, self.hi - rhs.lo)
+    }
+}
+impl<N: Real + PartialOrd> Mul for Interval<N> {
+    type Output = Interval<N>;
+    fn mul(self, rhs: Interval<N>) -> Interval<N> {
+        let (a, b, c, d) = (mul(self.lo, rhs.lo), mul(self.lo, rhs.hi), mul(self.hi, rhs.lo), mul(self.hi, rhs.hi));
+        Interval::new(min(min(a, b), min(c, d)), max(max(a, b), max(c, d)))
+    }
+}
+impl<N: Real + PartialOrd> Div for Interval<N> {
+    type Output = Interval<N>;
+    fn div(self, rhs: Interval<N>) -> Interval<N> {
+        let zero = N::int(0);
+        if rhs.contains(zero) {
+            // unbounded
+            let inf = N::int(1) / zero;
+            Interval::new(zero - inf, inf)
+        } else {
+            self * Interval::new(N::int(1) / rhs.hi, N::int(1) / rhs.lo)
+        }
+    }
+}
+impl<N: Real + PartialOrd> Neg for Interval<N> {
+    type Output = Interval<N>;
+    fn neg(self) -> Interval<N> {
+        let zero = N::int(0);
+        Interval::new(zero - self.hi, zero - self.lo)
+    }
+}
+
+impl<N: Real + PartialOrd> Add<N> for Interval<N> {
+    type Output = Interval<N>;
+    fn add(self, rhs: N) -> Interval<N> {
+        Interval::new(self.lo + rhs, self.hi + rhs)
+    }
+}
+impl<N: Real + PartialOrd> Sub<N> for Interval<N> {
+    type Output = Interval<N>;
+    fn sub(self, rhs: N) -> Interval<N> {
+        Interval::new(self.lo - rhs, self.hi - rhs)
+    }
+}
+impl<N: Real + PartialOrd> Mul<N> for Interval<N> {
+    type Output = Interval<N>;
+    fn mul(self, rhs: N) -> Interval<N> {
+        self * Interval::point(rhs)
+    }
+}
+impl<N: Real + PartialOrd> Div<N> for Interval<N> {
+    type Output = Interval<N>;
+    fn div(self, rhs: N) -> Interval<N> {
+        self / Interval::point(rhs)
+    }
+}
+
+impl<N: Real + PartialOrd> AddAssign for Interval<N> {
+    fn add_assign(&mut self, rhs: Interval<N>) {
+        *self = *self + rhs;
+    }
+}
+impl<N: Real + PartialOrd> SubAssign for Interval<N> {
+    fn sub_assign(&mut self, rhs: Interval<N>) {
+        *self = *self - rhs;
+    }
+}
+impl<N: Real + PartialOrd> MulAssign for Interval<N> {
+    fn mul_assign(&mut self, rhs: Interval<N>) {
+        *self = *self * rhs;
+    }
+}
+impl<N: Real + PartialOrd> DivAssign for Interval<N> {
+    fn div_assign(&mut self, rhs: Interval<N>) {
+        *self = *self / rhs;
+    }
+}
+
+#[test]
+fn test_interval() {
+    // 0 * inf is not NaN
+    let all = Interval::new(-1.0f64, 1.0) / Interval::new(-1.0, 1.0);
+    let r = all * Interval::new(0.0, 1.0);
+    assert_eq!(r.lo, ::std::f64::NEG_INFINITY);
+    assert_eq!(r.hi, ::std::f64::INFINITY);
+
+    // the peak of sin at pi/2, and of cos at 0
+    let s = Real::sin(Interval::new(1.0f64, 2.0));
+    assert_eq!(s.hi, 1.0);
+    assert!((s.lo - 1.0f64.sin()).abs() < 1e-12);
+    let c = Real::cos(Interval::new(-0.5f64, 0.25));
+    assert_eq!(c.hi, 1.0);
+    assert_eq!(c.lo, 0.5f64.cos());
+    
+    // far from zero, with a trough of sin at 2001.5 pi
+    let x = 2001.5 * ::std::f64::consts::PI;
+    let s = Real::sin(Interval::new(x - 0.1, x + 0.2));
+    assert_eq!(s.lo, -1.0);
+    assert_eq!(s.hi, (x + 0.2).sin().max((x - 0.1).sin()));
+    
+    // a whole period
+    let c = Real::cos(Interval::new(0.0f64, 7.0));
+    assert_eq!((c.lo, c.hi), (-1.0, 1.0));
+}
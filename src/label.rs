@@ -0,0 +1,305 @@
+use std::f32::consts::PI;
+use tuple::T2;
+use math::real::Real;
+use math::cast::Cast;
+use rusttype::{Font, Scale, Segment, point};
+use pen::Pen;
+use transform::Transform;
+
+// label geometry is in pixels, like markers
+type S = f32;
+
+/// Where a label goes on a line.
+#[derive(Copy, Clone, Debug)]
+pub struct Placement {
+    /// center of the text
+    pub center: T2<S, S>,
+    /// direction of the baseline, in radians
+    pub angle: S,
+    /// the part of the line (by length along it) covered by the label
+    pub gap: (S, S)
+}
+
+/// Text written along contour lines. All sizes are in pixels.
+pub struct Labels<'a> {
+    pub font: Font<'a>,
+    /// height of the font
+    pub size: S,
+    /// distance between labels on the same line
+    pub spacing: S,
+    /// space between the text and the line
+    pub padding: S,
+    /// largest change of direction of the line under a label, in radians
+    pub max_turn: S
+}
+
+// a polyline with the length along it at every point
+struct Arc {
+    points: Vec<T2<S, S>>,
+    s: Vec<S>
+}
+impl Arc {
+    fn new(points: &[T2<S, S>], closed: bool) -> Arc {
+        let mut points = points.to_vec();
+        if closed && !points.is_empty() {
+            let first = points[0];
+            points.push(first);
+        }
+        let mut s = Vec::with_capacity(points.len());
+        let mut length = 0.0;
+        for i in 0 .. points.len() {
+            if i > 0 {
+                let T2(dx, dy) = points[i] - points[i-1];
+                length += (dx * dx + dy * dy).sqrt();
+            }
+            s.push(length);
+        }
+        Arc { points: points, s: s }
+    }
+
+    fn length(&self) -> S {
+        self.s.last().cloned().unwrap_or(0.0)
+    }
+
+    fn at(&self, s: S) -> T2<S, S> {
+        let i = match self.s.iter().position(|&t| t > s) {
+            Some(0) => return self.points[0],
+            Some(i) => i,
+            None => return self.points[self.points.len() - 1]
+        };
+        let (s0, s1) = (self.s[i-1], self.s[i]);
+        let t = (s - s0) / (s1 - s0);
+        self.points[i-1] + (self.points[i] - self.points[i-1]) * t
+    }
+
+    // change of direction at every point
+    fn turns(&self, closed: bool) -> Vec<S> {
+        let n = self.points.len();
+        let direction = |i: usize| {
+            let T2(dx, dy) = self.points[i+1] - self.points[i];
+            dy.atan2(dx)
+        };
+        (0 .. n).map(|i| {
+            let (a, b) = if i > 0 && i + 1 < n {
+                (direction(i-1), direction(i))
+            } else if closed && n > 2 {
+                // the start and end point are the same
+                (direction(n-2), direction(0))
+            } else {
+                return 0.0;
+            };
+            let mut turn = (b - a).abs();
+            if turn > PI {
+                turn = 2.0 * PI - turn;
+            }
+            turn
+        }).collect()
+    }
+}
+
+impl<'a> Labels<'a> {
+    pub fn new(font: Font<'a>, size: S) -> Labels<'a> {
+        Labels {
+            font: font,
+            size: size,
+            spacing: 20.0 * size,
+            padding: 0.25 * size,
+            max_turn: 0.5
+        }
+    }
+
+    /// Length of `text` along the baseline.
+    pub fn width(&self, text: &str) -> S {
+        self.font.layout(text, Scale::uniform(self.size), point(0.0, 0.0))
+            .last()
+            .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+            .unwrap_or(0.0)
+    }
+
+    /// Positions for labels of length `width` along the line through `points`:
+    /// one per `spacing`, where the line is straightest, and only where it turns less than `max_turn`.
+    pub fn place(&self, points: &[T2<S, S>], closed: bool, width: S) -> Vec<Placement> {
+        place(points, closed, 0.5 * width + self.padding, self.spacing, self.max_turn)
+    }
+
+    /// Fill `text` at `placement`, with the weight of the pen.
+    pub fn draw<F, N>(&self, pen: &mut Pen<F, N>, text: &str, placement: &Placement)
+        where F: FnMut(T2<isize, isize>, N),
+              N: Real + PartialOrd + Cast<isize> + Cast<usize>,
+              usize: Cast<N>, isize: Cast<N>, S: Cast<N>
+    {
+        let n = |v: S| -> N { v.cast().unwrap() };
+        let width = self.width(text);
+
+        // centered on the line, with the middle of lower case letters on it
+        let T2(cx, cy) = placement.center;
+        let transform = Transform::translate(n(-0.5 * width), n(-0.25 * self.size))
+            .then(&Transform::rotate(n(placement.angle)))
+            .then(&Transform::translate(n(cx), n(cy)));
+        pen.push_transform(transform);
+
+        let mut contours: Vec<Vec<T2<N, N>>> = Vec::new();
+        for glyph in self.font.layout(text, Scale::uniform(self.size), point(0.0, 0.0)) {
+            let origin = glyph.position();
+            // glyph outlines have the y axis pointing down
+            let at = |p: ::rusttype::Point<S>| T2(n(origin.x + p.x), n(-(origin.y + p.y)));
+
+            for contour in glyph.unpositioned().shape().unwrap_or_else(Vec::new) {
+                let mut points = Vec::new();
+                for segment in contour.segments {
+                    match segment {
+                        Segment::Line(line) => points.push(at(line.p[0])),
+                        Segment::Curve(curve) => {
+                            let (p0, p1, p2) = (curve.p[0], curve.p[1], curve.p[2]);
+                            // a quarter pixel is fine enough
+                            let T2(dx, dy) = T2(p0.x - 2.0 * p1.x + p2.x, p0.y - 2.0 * p1.y + p2.y);
+                            let steps = ((dx * dx + dy * dy).sqrt().sqrt() * 2.0).ceil().max(1.0) as usize;
+                            for i in 0 .. steps {
+                                let t = i as S / steps as S;
+                                let u = 1.0 - t;
+                                points.push(at(point(
+                                    u * u * p0.x + 2.0 * u * t * p1.x + t * t * p2.x,
+                                    u * u * p0.y + 2.0 * u * t * p1.y + t * t * p2.y
+                                )));
+                            }
+                        }
+                    }
+                }
+                contours.push(points);
+            }
+        }
+        pen.fill(&contours);
+        pen.pop_transform();
+    }
+}
+
+// Labels::place, for labels covering `half` of the line on either side of their center
+fn place(points: &[T2<S, S>], closed: bool, half: S, spacing: S, max_turn: S) -> Vec<Placement> {
+    let arc = Arc::new(points, closed);
+    let length = arc.length();
+    if length < 2.0 * half {
+        return vec![];
+    }
+
+    let turns = arc.turns(closed);
+    let turn_under = |s: S| -> S {
+        arc.s.iter().zip(turns.iter())
+            .filter(|&(&t, _)| t > s - half && t < s + half)
+            .map(|(_, &turn)| turn)
+            .sum()
+    };
+
+    let mut placements: Vec<Placement> = Vec::new();
+    let mut chunk = half;
+    while chunk <= length - half {
+        // the straightest position in this chunk, in steps of a pixel
+        let mut best: Option<(S, S)> = None;
+        let mut s = chunk;
+        while s < chunk + spacing && s <= length - half {
+            let turn = turn_under(s);
+            if best.map(|(_, t)| turn < t).unwrap_or(true) {
+                best = Some((s, turn));
+            }
+            s += 1.0;
+        }
+        chunk += spacing;
+
+        let (s, turn) = match best {
+            Some(b) => b,
+            None => break
+        };
+        let far_enough = placements.last().map(|p| s - p.gap.1 >= half).unwrap_or(true);
+        // on closed lines the first label follows the last one
+        let wraps = closed && placements.first().map(|p| length - s + p.gap.0 < 2.0 * half).unwrap_or(false);
+        if turn > max_turn || !far_enough || wraps {
+            continue;
+        }
+
+        // upright text
+        let T2(dx, dy) = arc.at(s + half) - arc.at(s - half);
+        let mut angle = dy.atan2(dx);
+        if dx < 0.0 {
+            angle += PI;
+        }
+        placements.push(Placement {
+            center: arc.at(s),
+            angle: angle,
+            gap: (s - half, s + half)
+        });
+    }
+    placements
+}
+
+/// The parts of the line through `points` outside of the `gaps` (by length along the line, in increasing order).
+pub fn cut(points: &[T2<S, S>], closed: bool, gaps: &[(S, S)]) -> Vec<Vec<T2<S, S>>> {
+    let arc = Arc::new(points, closed);
+    if gaps.is_empty() {
+        return vec![arc.points];
+    }
+
+    // the part from a to b
+    let piece = |a: S, b: S| -> Vec<T2<S, S>> {
+        let mut piece = vec![arc.at(a)];
+        for (&p, &s) in arc.points.iter().zip(arc.s.iter()) {
+            if s > a && s < b {
+                piece.push(p);
+            }
+        }
+        piece.push(arc.at(b));
+        piece
+    };
+
+    let mut pieces = Vec::new();
+    let mut start = 0.0;
+    for &(a, b) in gaps {
+        if a > start {
+            pieces.push(piece(start, a));
+        }
+        start = b;
+    }
+    let length = arc.length();
+    if start < length {
+        let last = piece(start, length);
+        if closed && gaps[0].0 > 0.0 {
+            // continues with the first piece
+            let first = pieces.remove(0);
+            let mut last = last;
+            last.extend(first.into_iter().skip(1));
+            pieces.push(last);
+        } else {
+            pieces.push(last);
+        }
+    }
+    pieces
+}
+
+#[test]
+fn test_place() {
+    // one label per spacing on a straight line
+    let line = [T2(0.0, 0.0), T2(200.0, 0.0)];
+    let starts: Vec<S> = place(&line, false, 5.0, 50.0, 0.5).iter().map(|p| p.gap.0).collect();
+    assert_eq!(starts, vec![0.0, 50.0, 100.0, 150.0]);
+
+    // the last label would end 2 pixels before the first one starts
+    let square = [T2(0.0, 0.0), T2(40.5, 0.0), T2(40.5, 40.5), T2(0.0, 40.5)];
+    let placements = place(&square, true, 5.0, 50.0, 0.5);
+    let gaps: Vec<(S, S)> = placements.iter().map(|p| p.gap).collect();
+    assert_eq!(gaps, vec![(0.0, 10.0), (50.0, 60.0), (100.0, 110.0)]);
+}
+
+#[test]
+fn test_cut() {
+    let line = [T2(0.0, 0.0), T2(100.0, 0.0)];
+    let pieces = cut(&line, false, &[(10.0, 20.0), (50.0, 60.0)]);
+    let ends: Vec<(S, S)> = pieces.iter().map(|p| (p[0].0, p[p.len() - 1].0)).collect();
+    assert_eq!(ends, vec![(0.0, 10.0), (20.0, 50.0), (60.0, 100.0)]);
+
+    // the piece after the last gap continues with the one before the first gap
+    let square = [T2(0.0, 0.0), T2(10.0, 0.0), T2(10.0, 10.0), T2(0.0, 10.0)];
+    let pieces = cut(&square, true, &[(5.0, 15.0), (20.0, 25.0)]);
+    assert_eq!(pieces.len(), 2);
+    let ends = |p: &Vec<T2<S, S>>| ((p[0].0, p[0].1), (p[p.len() - 1].0, p[p.len() - 1].1));
+    assert_eq!(ends(&pieces[0]), ((10.0, 5.0), (10.0, 10.0)));
+    assert_eq!(ends(&pieces[1]), ((5.0, 10.0), (5.0, 0.0)));
+    assert_eq!(pieces[1].len(), 4);
+}
@@ -8,6 +8,9 @@ extern crate image;
 extern crate tuple;
 extern crate palette;
 extern crate math;
+extern crate crossbeam;
+extern crate simd;
+extern crate rusttype;
 #[macro_use] extern crate lazy_static;
 
 pub mod canvas;
@@ -15,7 +18,18 @@ pub mod plot;
 pub mod array;
 pub mod contour;
 pub mod pen;
+pub mod area;
 pub mod colormap;
 pub mod rng;
+pub mod transform;
+pub mod svg;
+pub mod marker;
+pub mod interval;
+pub mod sampler;
+pub mod npy;
+pub mod label;
+pub mod trace;
+pub mod dual;
+mod util;
 
 pub use canvas::Canvas;
@@ -0,0 +1,144 @@
+use std::f32::consts::PI;
+use tuple::T2;
+use math::real::Real;
+use math::cast::Cast;
+use pen::Pen;
+
+// marker geometry is in pixels, so single precision is plenty
+type S = f32;
+
+/// Shape drawn at every point of a scatter plot. All sizes are in pixels.
+#[derive(Clone, Debug)]
+pub enum Marker {
+    /// outline of a circle with the given diameter
+    Circle(S),
+    /// outline of an axis aligned square with the given side length
+    Square(S),
+    /// diagonal cross (×) with the given width
+    Cross(S),
+    /// upright cross (+) with the given width
+    Plus(S),
+    /// outline of an upward pointing equilateral triangle with the given side length
+    Triangle(S),
+    /// `width` × `height` coverage values, row by row from the top, centered on the point
+    Bitmap { width: usize, height: usize, data: Vec<S> }
+}
+
+impl Marker {
+    /// Draw the marker centered at `p` (in pixels). Nothing is drawn at NaN or infinite points.
+    pub fn draw<F, N>(&self, pen: &mut Pen<F, N>, p: T2<N, N>)
+        where F: FnMut(T2<isize, isize>, N),
+              N: Real + PartialOrd + Cast<isize> + Cast<usize>,
+              usize: Cast<N>, isize: Cast<N>, S: Cast<N>
+    {
+        let zero = N::int(0);
+        if !(p.0 - p.0 == zero && p.1 - p.1 == zero) {
+            return;
+        }
+
+        // offset from the center
+        let at = |x: S, y: S| -> T2<N, N> {
+            let d: T2<N, N> = T2(x, y).cast().unwrap();
+            p + d
+        };
+        match *self {
+            Marker::Circle(size) => {
+                let r = 0.5 * size;
+                // enough segments to stay within a quarter pixel of the circle
+                let n = (PI / (1.0 - 0.25 / r.max(0.25)).acos()).ceil().max(8.0) as usize;
+                pen.move_to(at(r, 0.0));
+                for i in 1 ... n {
+                    let (s, c) = (2.0 * PI * i as S / n as S).sin_cos();
+                    pen.line_to(at(r * c, r * s));
+                }
+            },
+            Marker::Square(size) => {
+                let h = 0.5 * size;
+                pen.move_to(at(-h, -h));
+                pen.line_to(at(h, -h));
+                pen.line_to(at(h, h));
+                pen.line_to(at(-h, h));
+                pen.close_path();
+            },
+            Marker::Cross(size) => {
+                let h = 0.5 * size;
+                pen.line(at(-h, -h), at(h, h));
+                pen.line(at(-h, h), at(h, -h));
+            },
+            Marker::Plus(size) => {
+                let h = 0.5 * size;
+                pen.line(at(-h, 0.0), at(h, 0.0));
+                pen.line(at(0.0, -h), at(0.0, h));
+            },
+            Marker::Triangle(size) => {
+                // centered on the centroid
+                let r = size / 3.0f32.sqrt();
+                pen.move_to(at(0.0, r));
+                pen.line_to(at(-0.5 * size, -0.5 * r));
+                pen.line_to(at(0.5 * size, -0.5 * r));
+                pen.close_path();
+            },
+            Marker::Bitmap { width, height, ref data } => {
+                assert_eq!(data.len(), width * height);
+
+                // the y axis points up, so the first row is the top one
+                let T2(x, y): T2<isize, isize> = match p.cast() {
+                    Some(p) => p,
+                    None => return // too far out
+                };
+                let x0 = x - (width as isize - 1) / 2;
+                let y0 = y + (height as isize - 1) / 2;
+                for (row, line) in data.chunks(width).enumerate() {
+                    for (col, &v) in line.iter().enumerate() {
+                        if v != 0.0 {
+                            pen.plot(T2(x0 + col as isize, y0 - row as isize), v.cast().unwrap());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn stamp(marker: &Marker, center: T2<S, S>) -> Vec<(T2<isize, isize>, S)> {
+    let mut pixels = Vec::new();
+    {
+        let mut pen = Pen::new(|p, v| pixels.push((p, v)));
+        pen.set_clip(T2(0.0, 0.0), T2(24.0, 24.0));
+        pen.set_raster(::pen::Raster::Area(1.0));
+        marker.draw(&mut pen, center);
+        pen.flush();
+    }
+    pixels
+}
+
+#[test]
+fn test_markers() {
+    // the outlines cover their length, around the center
+    let center = T2(10.3, 12.6);
+    for &(ref marker, length) in &[
+        (Marker::Circle(10.0), 10.0 * PI),
+        (Marker::Square(8.0), 32.0),
+        (Marker::Cross(8.0), 16.0 * 2.0f32.sqrt()),
+        (Marker::Plus(8.0), 16.0),
+        (Marker::Triangle(9.0), 27.0)
+    ] {
+        let pixels = stamp(marker, center);
+        let total: S = pixels.iter().map(|&(_, v)| v).sum();
+        let x = pixels.iter().map(|&(p, v)| p.0 as S * v).sum::<S>() / total;
+        let y = pixels.iter().map(|&(p, v)| p.1 as S * v).sum::<S>() / total;
+        assert!((total / length - 1.0).abs() < 0.1, "{:?}: coverage {} for a length of {}", marker, total, length);
+        assert!((x - center.0).abs() < 0.2 && (y - center.1).abs() < 0.2, "{:?}: centered at {}, {}", marker, x, y);
+
+        // and nothing at NaN or infinite points
+        assert!(stamp(marker, T2(::std::f32::NAN, 3.0)).is_empty());
+        assert!(stamp(marker, T2(3.0, ::std::f32::INFINITY)).is_empty());
+    }
+
+    // bitmaps are centered on the pixel of the point, with the first row on top
+    let bitmap = Marker::Bitmap { width: 3, height: 3, data: vec![0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0] };
+    assert_eq!(stamp(&bitmap, T2(10.0, 12.0)), vec![(T2(10, 13), 1.0), (T2(10, 11), 0.5)]);
+    assert!(stamp(&bitmap, T2(1e30, 3.0)).is_empty());
+    assert!(stamp(&bitmap, T2(::std::f32::NAN, 3.0)).is_empty());
+}
@@ -0,0 +1,126 @@
+use std::io::{self, Read};
+use array::{Array, RowMajor};
+use canvas::Meta;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// the text after `'key':` in the header dictionary
+fn field<'a>(header: &'a str, key: &str) -> io::Result<&'a str> {
+    let pattern = format!("'{}':", key);
+    match header.find(&pattern) {
+        Some(pos) => Ok(header[pos + pattern.len() ..].trim_left()),
+        None => Err(invalid("missing field in npy header"))
+    }
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |v, &b| (v << 8) | b as u64)
+}
+
+/// Read a two-dimensional array from a NumPy `.npy` file.
+/// The first row is the top (y = height - 1), like in `Array::from_image`.
+/// Supports little endian floats and integers, converted to `f32`.
+pub fn read_npy<R: Read>(mut r: R) -> io::Result<Array<Vec<f32>, RowMajor>> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic[.. 6] != b"\x93NUMPY" {
+        return Err(invalid("not a npy file"));
+    }
+    let header_len = match magic[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            r.read_exact(&mut len)?;
+            le_u64(&len) as usize
+        },
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            r.read_exact(&mut len)?;
+            le_u64(&len) as usize
+        },
+        _ => return Err(invalid("unsupported npy version"))
+    };
+    let mut header = vec![0; header_len];
+    r.read_exact(&mut header)?;
+    let header = String::from_utf8(header).map_err(|_| invalid("npy header is not text"))?;
+
+    // {'descr': '<f4', 'fortran_order': False, 'shape': (3, 4), }
+    let descr = field(&header, "descr")?;
+    let descr = descr.trim_left_matches('\'').split('\'').next().unwrap_or("");
+    let fortran_order = field(&header, "fortran_order")?.starts_with("True");
+    let shape = field(&header, "shape")?;
+    let shape: Vec<usize> = shape.trim_left_matches('(').split(')').next().unwrap_or("")
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| invalid("invalid shape in npy header")))
+        .collect::<Result<_, _>>()?;
+    let (height, width) = match shape.len() {
+        2 => (shape[0], shape[1]),
+        _ => return Err(invalid("npy array is not two-dimensional"))
+    };
+
+    if descr.len() < 3 {
+        return Err(invalid("unsupported npy data type"));
+    }
+    let (order, kind) = (&descr[.. 1], &descr[1 .. 2]);
+    let size: usize = descr[2 ..].parse().map_err(|_| invalid("unsupported npy data type"))?;
+    if size == 0 || size > 8 {
+        return Err(invalid("unsupported npy data type"));
+    }
+    if order == ">" && size > 1 {
+        return Err(invalid("big endian npy files are not supported"));
+    }
+    let convert: fn(&[u8]) -> f32 = match (kind, size) {
+        ("f", 4) => |b| f32::from_bits(le_u64(b) as u32),
+        ("f", 8) => |b| f64::from_bits(le_u64(b)) as f32,
+        ("u", _) => |b| le_u64(b) as f32,
+        ("i", _) => |b| {
+            // sign extension
+            let shift = 64 - 8 * b.len();
+            ((le_u64(b) << shift) as i64 >> shift) as f32
+        },
+        _ => return Err(invalid("unsupported npy data type"))
+    };
+
+    let mut bytes = vec![0; width * height * size];
+    r.read_exact(&mut bytes)?;
+    let values: Vec<f32> = bytes.chunks(size).map(convert).collect();
+
+    let mut data = vec![0.0; width * height];
+    for y in 0 .. height {
+        let row = height - 1 - y;
+        for x in 0 .. width {
+            data[x + y * width] = if fortran_order {
+                // column by column
+                values[row + x * height]
+            } else {
+                values[x + row * width]
+            };
+        }
+    }
+
+    Ok(Array {
+        meta:   RowMajor::new(width, height),
+        data:   data
+    })
+}
+
+#[test]
+fn test_read_npy() {
+    let mut file = b"\x93NUMPY\x01\x00".to_vec();
+    let header = "{'descr': '<i2', 'fortran_order': False, 'shape': (2, 3), }\n";
+    file.push(header.len() as u8);
+    file.push(0);
+    file.extend(header.bytes());
+    for &v in &[1i16, 2, 3, -4, -5, -6] {
+        file.push(v as u8);
+        file.push((v >> 8) as u8);
+    }
+
+    let array = read_npy(&file[..]).unwrap();
+    // the first row is at the top
+    assert_eq!(array[(2, 1)], 3.0);
+    assert_eq!(array[(0, 0)], -4.0);
+}
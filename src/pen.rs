@@ -1,67 +1,582 @@
 use std::mem::swap;
 use tuple::T2;
+use math::real::Real;
 use math::cast::Cast;
+use util::{max, min, ceil};
+use transform::Transform;
+use area::Accumulator;
 
-type N = f32;
+/// How `Pen` turns lines into pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Raster<N> {
+    /// Xiaolin Wu's line algorithm, fast but with a line weight that depends on the slope
+    Wu,
+    /// exact coverage of a stroke with the given width, through signed area accumulation
+    Area(N)
+}
 
-pub struct Pen<F> {
+pub struct Pen<F, N: Real = f32> {
     draw:   F,
-    p:      T2<N, N>
+    p:      T2<N, N>,
+    start:  T2<N, N>, // start of the current subpath
+    
+    // from user to device (pixel) coordinates, and the saved ones below it
+    transform:  Transform<N>,
+    stack:      Vec<Transform<N>>,
+    
+    dash:   Option<Dash<N>>,
+    clip:   Option<(T2<N, N>, T2<N, N>)>,
+    weight: N,
+    
+    // polyline mode: moves below the threshold are collected here
+    polyline:   bool,
+    pending:    Option<(T2<N, N>, N)>,
+    
+    raster:     Raster<N>,
+    acc:        Option<Accumulator<N>>, // created on demand, over the clip rectangle
+    acc_weight: Option<N>,              // intensity of the coverage in the buffer, if any
+    joint:      Option<Stroke<N>>       // last segment of the path, waiting for the next one
+}
+
+/// A segment of a stroke in `Raster::Area` mode. Its end is drawn once the next segment is known:
+/// where they meet, both are cut along the bisector, so they cover the joint exactly once.
+#[derive(Copy, Clone)]
+struct Stroke<N> {
+    start:  (T2<N, N>, T2<N, N>), // left and right corner at the start
+    end:    T2<N, N>,
+    n:      T2<N, N>,             // normal to the left, half the width long
+    length: N,
+    intensity: N
+}
+
+/// Dash pattern state. Even entries of the pattern are drawn, odd entries are gaps.
+#[derive(Clone)]
+struct Dash<N> {
+    pattern:    Vec<N>,
+    offset:     N,
+    index:      usize, // current entry of the pattern
+    left:       N      // length left in the current entry
+}
+
+impl<N> Dash<N> where N: Real + PartialOrd {
+    fn new(mut pattern: Vec<N>, offset: N) -> Dash<N> {
+        assert!(pattern.iter().all(|&l| l >= N::int(0)), "negative dash length");
+        
+        // like SVG, an odd number of entries is repeated once
+        if pattern.len() % 2 == 1 {
+            let copy = pattern.clone();
+            pattern.extend(copy);
+        }
+        
+        let mut dash = Dash {
+            pattern:    pattern,
+            offset:     offset,
+            index:      0,
+            left:       N::int(0)
+        };
+        assert!(dash.total() > N::int(0), "dash pattern has zero length");
+        dash.reset();
+        dash
+    }
+    
+    fn total(&self) -> N {
+        self.pattern.iter().fold(N::int(0), |sum, &l| sum + l)
+    }
+    
+    // rewind to the beginning of the pattern, shifted by the offset
+    fn reset(&mut self) {
+        let total = self.total();
+        let mut phase = self.offset - (self.offset / total).floor() * total;
+        
+        self.index = 0;
+        while phase >= self.pattern[self.index] {
+            phase = phase - self.pattern[self.index];
+            self.index = (self.index + 1) % self.pattern.len();
+        }
+        self.left = self.pattern[self.index] - phase;
+    }
+    
+    #[inline]
+    fn advance(&mut self) {
+        self.index = (self.index + 1) % self.pattern.len();
+        self.left = self.pattern[self.index];
+    }
+    
+    #[inline]
+    fn on(&self) -> bool {
+        self.index % 2 == 0
+    }
+}
+
+// http://en.wikipedia.org/wiki/Liang%E2%80%93Barsky_algorithm
+// returns the part of the line p0 -> p1 inside the rectangle min ... max, if any.
+// Lines with a NaN or infinite end have none.
+fn clip_line<N>(min: T2<N, N>, max: T2<N, N>, p0: T2<N, N>, p1: T2<N, N>) -> Option<(T2<N, N>, T2<N, N>)>
+    where N: Real + PartialOrd
+{
+    if !(finite(p0) && finite(p1)) {
+        return None;
+    }
+    let zero = N::int(0);
+    let T2(x0, y0) = p0;
+    let T2(dx, dy) = p1 - p0;
+    let mut t0 = zero;
+    let mut t1 = N::int(1);
+    
+    for &(p, q) in &[(-dx, x0 - min.0), (dx, max.0 - x0), (-dy, y0 - min.1), (dy, max.1 - y0)] {
+        if p == zero {
+            // parallel to this edge
+            if q < zero {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if r != r {
+                return None;
+            }
+            if p < zero {
+                if r > t1 {
+                    return None;
+                }
+                t0 = self::max(t0, r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = self::min(t1, r);
+            }
+        }
+    }
+    
+    Some((
+        T2(x0 + t0 * dx, y0 + t0 * dy),
+        T2(x0 + t1 * dx, y0 + t1 * dy)
+    ))
+}
+
+// number of line segments needed for a curve whose second difference is `dd`
+#[inline]
+fn curve_segments<N>(dd: T2<N, N>, factor: N) -> usize
+    where N: Real + Cast<usize>
+{
+    // maximal distance of flattened curves from the real curve, in pixels
+    let tolerance = N::int(1) / N::int(4);
+    
+    let T2(x, y) = dd;
+    let deviation = factor * (x * x + y * y).sqrt();
+    let n: usize = ipart((deviation / tolerance).sqrt() + N::int(1)).cast().unwrap();
+    n.max(1)
+}
+
+// neither coordinate is NaN or infinite
+#[inline(always)]
+fn finite<N: Real + PartialOrd>(p: T2<N, N>) -> bool {
+    let zero = N::int(0);
+    p.0 - p.0 == zero && p.1 - p.1 == zero
 }
 
 #[inline(always)]
-fn ipart(x: N) -> N {
+fn ipart<N: Real>(x: N) -> N {
     x.floor()
 }
 
 #[inline(always)]
-fn round(x: N) -> N {
-    x.round()
+fn round<N: Real>(x: N) -> N {
+    (x + N::int(1) / N::int(2)).floor()
 }
 
 #[inline(always)]
-fn fpart(x: N) -> N {
-    x.fract()
+fn fpart<N: Real>(x: N) -> N {
+    x - x.floor()
 }
 
 #[inline(always)]
-fn rfpart(x: N) -> N {
-    1.0 - x.fract()
+fn rfpart<N: Real>(x: N) -> N {
+    N::int(1) - fpart(x)
 }
 
-impl<F> Pen<F> where F: FnMut(T2<isize, isize>, N)
+impl<F, N> Pen<F, N> where
+    F: FnMut(T2<isize, isize>, N),
+    N: Real + PartialOrd + Cast<isize> + Cast<usize>,
+    usize: Cast<N>, isize: Cast<N>
 {
-    pub fn new(draw: F) -> Pen<F> {
+    pub fn new(draw: F) -> Pen<F, N> {
         Pen {
             draw:   draw,
-            p:      T2(0.0, 0.0)
+            p:      T2(N::int(0), N::int(0)),
+            start:  T2(N::int(0), N::int(0)),
+            transform:  Transform::identity(),
+            stack:      Vec::new(),
+            dash:   None,
+            clip:   None,
+            weight: N::int(1),
+            polyline:   false,
+            pending:    None,
+            raster:     Raster::Wu,
+            acc:        None,
+            acc_weight: None,
+            joint:      None
         }
     }
+    
+    /// A new pen drawing to `draw`, with the transforms, dash pattern, clip rectangle,
+    /// weight, polyline mode and rasterizer of this one.
+    pub fn fork<G>(&self, draw: G) -> Pen<G, N> where G: FnMut(T2<isize, isize>, N) {
+        Pen {
+            draw:   draw,
+            p:      self.p,
+            start:  self.start,
+            transform:  self.transform,
+            stack:      self.stack.clone(),
+            dash:   self.dash.clone(),
+            clip:   self.clip,
+            weight: self.weight,
+            polyline:   self.polyline,
+            pending:    None,
+            raster:     self.raster,
+            acc:        None,
+            acc_weight: None,
+            joint:      None
+        }
+    }
+    
+    /// Select the rasterizer for strokes. `Raster::Area` needs a clip rectangle.
+    /// Its strokes are only drawn at the end of the path: by `move_to`, `fill`, `line` or `flush`.
+    pub fn set_raster(&mut self, raster: Raster<N>) {
+        self.flush();
+        self.raster = raster;
+        self.acc = None;
+    }
+    
+    // the accumulation buffer, big enough for the clip rectangle and the stroke width
+    fn accumulator(&mut self) -> &mut Accumulator<N> {
+        if self.acc.is_none() {
+            let (min, max) = self.clip.expect("area rasterization needs a clip rectangle");
+            let margin = match self.raster {
+                Raster::Area(width) => ceil(width / N::int(2)).cast().unwrap(),
+                Raster::Wu => 0
+            };
+            self.acc = Some(Accumulator::new(min, max, margin + 2));
+        }
+        self.acc.as_mut().unwrap()
+    }
+    
+    // hand the accumulated coverage to the draw function
+    fn resolve(&mut self, scale: N) {
+        if let Some(ref mut acc) = self.acc {
+            let draw = &mut self.draw;
+            acc.resolve(scale, |p, v| draw(p, v));
+        }
+    }
+    
+    /// Fill the area enclosed by the closed `contours`, with exact coverage.
+    /// Overlapping areas are covered only once. Needs a clip rectangle.
+    pub fn fill<I, P>(&mut self, contours: I)
+        where I: IntoIterator<Item=P>, P: AsRef<[T2<N, N>]>
+    {
+        // strokes and fills must not mix in the buffer
+        self.flush();
+        
+        let transform = self.transform;
+        let weight = self.weight;
+        {
+            let acc = self.accumulator();
+            for contour in contours {
+                let points = contour.as_ref();
+                if let Some(&last) = points.last() {
+                    let mut prev = transform.apply(last);
+                    for &p in points {
+                        let p = transform.apply(p);
+                        acc.line(prev, p);
+                        prev = p;
+                    }
+                }
+            }
+        }
+        self.resolve(weight);
+    }
+    
+    /// In polyline mode moves shorter than the threshold are not lost, but merged
+    /// into the next segment, or drawn by `flush` at the end of the path.
+    pub fn set_polyline(&mut self, polyline: bool) {
+        self.flush();
+        self.polyline = polyline;
+    }
+    
+    /// Draw what is left of the current path.
+    pub fn flush(&mut self) {
+        if let Some((p, w)) = self.pending.take() {
+            self.stroke(p, w);
+        }
+        if let Some(s) = self.joint.take() {
+            self.area_quad(s, (s.end + s.n, s.end - s.n));
+        }
+        
+        if let Some(weight) = self.acc_weight.take() {
+            self.resolve(weight);
+        }
+    }
+    
+    /// Scale the coverage of everything drawn from now on by `weight`.
+    pub fn set_weight(&mut self, weight: N) {
+        self.weight = weight;
+    }
+    
+    /// Only rasterize the parts of lines within `min ... max` (in pixels).
+    /// The draw function may still see pixels just outside of the rectangle.
+    pub fn set_clip(&mut self, min: T2<N, N>, max: T2<N, N>) {
+        self.flush();
+        self.clip = Some((min, max));
+        self.acc = None;
+    }
+    
+    pub fn clear_clip(&mut self) {
+        self.flush();
+        self.clip = None;
+        self.acc = None;
+    }
+    
+    /// The clip rectangle, if any.
+    pub fn clip(&self) -> Option<(T2<N, N>, T2<N, N>)> {
+        self.clip
+    }
+    
+    /// Stroke with alternating on/off lengths (in pixels), starting `offset` into the pattern.
+    /// The phase is carried across `line_to` calls and restarts at every `move_to`.
+    pub fn set_dash(&mut self, pattern: &[N], offset: N) {
+        self.dash = Some(Dash::new(pattern.to_vec(), offset));
+    }
+    
+    /// Go back to solid lines.
+    pub fn clear_dash(&mut self) {
+        self.dash = None;
+    }
+    
+    /// Map all following coordinates through `m` before the current transform.
+    pub fn push_transform(&mut self, m: Transform<N>) {
+        let outer = self.transform;
+        self.stack.push(outer);
+        self.transform = m.then(&outer);
+    }
+    
+    /// Restore the transform from before the matching `push_transform`.
+    pub fn pop_transform(&mut self) {
+        self.transform = self.stack.pop().expect("pop_transform without push_transform");
+    }
+    
+    /// The current map from user to device coordinates.
+    pub fn transform(&self) -> Transform<N> {
+        self.transform
+    }
+    
+    /// Deposit `v` (times the pen weight) at a single pixel. No transform is applied.
+    #[inline]
+    pub fn plot(&mut self, p: T2<isize, isize>, v: N) {
+        (self.draw)(p, v * self.weight);
+    }
+    
+    /// Hand `v` to the draw function as it is, without the pen weight.
+    #[inline]
+    pub fn plot_raw(&mut self, p: T2<isize, isize>, v: N) {
+        (self.draw)(p, v);
+    }
+    
+    /// A whole path from `p0` to `p1`.
     pub fn line(&mut self, p0: T2<N, N>, p1: T2<N, N>) {
         self.move_to(p0);
         self.line_to(p1);
+        self.flush();
     }
     #[inline]
     pub fn move_to(&mut self, p: T2<N, N>) {
+        let p = self.transform.apply(p);
+        self.flush();
         self.p = p;
+        self.start = p;
+        if let Some(ref mut dash) = self.dash {
+            dash.reset();
+        }
     }
     #[inline]
     pub fn line_to(&mut self, p: T2<N, N>) {
-        let T2(mut x0, mut y0) = self.p;
-        let T2(mut x1, mut y1) = p;
-        let half = 0.5;
-        let threshold = 0.1;
+        self.line_to_weighted(p, N::int(1));
+    }
+    
+    /// Line back to the point of the last `move_to`.
+    pub fn close_path(&mut self) {
+        let start = self.start;
+        self.device_line_to(start, N::int(1));
+    }
+    
+    /// Quadratic Bézier curve from the current position to `p` with control point `c`.
+    pub fn quad_to(&mut self, c: T2<N, N>, p: T2<N, N>) {
+        // affine maps keep Bézier curves, so the control points can be mapped
+        let (c, p) = (self.transform.apply(c), self.transform.apply(p));
+        let (one, two) = (N::int(1), N::int(2));
+        let p0 = self.p;
+        let n = curve_segments(p0 - c * two + p, one / N::int(4));
+        let steps: N = n.cast().unwrap();
+        for i in 1 ... n {
+            let i: N = i.cast().unwrap();
+            let t = i / steps;
+            let s = one - t;
+            self.device_line_to(p0 * (s * s) + c * (two * s * t) + p * (t * t), one);
+        }
+    }
+    
+    /// Cubic Bézier curve from the current position to `p` with control points `c1` and `c2`.
+    pub fn cubic_to(&mut self, c1: T2<N, N>, c2: T2<N, N>, p: T2<N, N>) {
+        let (c1, c2, p) = (self.transform.apply(c1), self.transform.apply(c2), self.transform.apply(p));
+        let (one, two, three) = (N::int(1), N::int(2), N::int(3));
+        let p0 = self.p;
+        let T2(ax, ay) = p0 - c1 * two + c2;
+        let T2(bx, by) = c1 - c2 * two + p;
+        let dd = T2(max(ax.abs(), bx.abs()), max(ay.abs(), by.abs()));
+        let n = curve_segments(dd, three / N::int(4));
+        let steps: N = n.cast().unwrap();
+        for i in 1 ... n {
+            let i: N = i.cast().unwrap();
+            let t = i / steps;
+            let s = one - t;
+            self.device_line_to(p0 * (s * s * s) + c1 * (three * s * s * t) + c2 * (three * s * t * t) + p * (t * t * t), one);
+        }
+    }
+    
+    /// Like `line_to`, with the coverage scaled by `w` (on top of the pen weight).
+    pub fn line_to_weighted(&mut self, p: T2<N, N>, w: N) {
+        let p = self.transform.apply(p);
+        self.device_line_to(p, w);
+    }
+    
+    // p is in device coordinates
+    fn device_line_to(&mut self, p: T2<N, N>, w: N) {
+        let T2(dx, dy) = p - self.p;
+        let threshold = N::int(1) / N::int(10);
         
-        if (x1 - x0).abs().max((y1 - y0).abs()) < threshold {
+        if max(dx.abs(), dy.abs()) < threshold {
+            if self.polyline {
+                // keep self.p, so the next segment starts where the last one ended
+                self.pending = Some((p, w));
+            }
             return;
         }
         
+        self.pending = None;
+        self.stroke(p, w);
+    }
+    
+    // draw from the current position to p, applying the dash pattern
+    fn stroke(&mut self, p: T2<N, N>, w: N) {
+        let zero = N::int(0);
+        let T2(x0, y0) = self.p;
+        let T2(dx, dy) = p - self.p;
+        
         // save values
         self.p = p;
         
+        let mut dash = match self.dash.take() {
+            Some(dash) => dash,
+            None => {
+                self.segment(T2(x0, y0), p, w);
+                return;
+            }
+        };
+        
+        // walk along the line and only draw the pieces where the pattern is on
+        let length = (dx * dx + dy * dy).sqrt();
+        let mut t = zero;
+        while t < length {
+            let step = min(dash.left, length - t);
+            if dash.on() && step > zero {
+                let (a, b) = (t / length, (t + step) / length);
+                self.segment(T2(x0 + a * dx, y0 + a * dy), T2(x0 + b * dx, y0 + b * dy), w);
+            }
+            t = t + step;
+            dash.left = dash.left - step;
+            if dash.left <= zero {
+                dash.advance();
+            }
+        }
+        self.dash = Some(dash);
+    }
+    
+    // the rectangle around the line, joined to the last segment if it ends at p0
+    fn area_segment(&mut self, p0: T2<N, N>, p1: T2<N, N>, width: N, intensity: N) {
+        let (zero, one) = (N::int(0), N::int(1));
+        let T2(dx, dy) = p1 - p0;
+        let length = (dx * dx + dy * dy).sqrt();
+        if !(length > zero) {
+            return;
+        }
+        let h = width / (length + length);
+        let n = T2(zero - dy * h, dx * h);
+        
+        let mut start = (p0 + n, p0 - n);
+        if let Some(last) = self.joint.take() {
+            // cos and sin of the turn, and tan of half of it
+            let hw2 = width * width / N::int(4);
+            let cos = (last.n.0 * n.0 + last.n.1 * n.1) / hw2;
+            let sin = (last.n.0 * n.1 - last.n.1 * n.0) / hw2;
+            let tan = sin.abs() / (one + cos);
+            
+            // The inner corner moves back by tan * width / 2. Up to a turn of 120°,
+            // and as long as that is within half of both segments, the ends are cut.
+            let fits = tan * width <= min(length, last.length);
+            let end = if last.end == p0 && last.intensity == intensity && cos + cos + one >= zero && fits {
+                let m = (last.n + n) * (one / (one + cos));
+                start = (p0 + m, p0 - m);
+                start
+            } else {
+                (last.end + last.n, last.end - last.n)
+            };
+            self.area_quad(last, end);
+        }
+        self.joint = Some(Stroke { start: start, end: p1, n: n, length: length, intensity: intensity });
+    }
+    
+    // add the quadrilateral of s, with the given corners at the end, to the buffer
+    fn area_quad(&mut self, s: Stroke<N>, end: (T2<N, N>, T2<N, N>)) {
+        // the buffer holds the coverage of one intensity at a time
+        match self.acc_weight {
+            Some(w) if w != s.intensity => self.resolve(w),
+            _ => ()
+        }
+        self.acc_weight = Some(s.intensity);
+        
+        // all of them have the same orientation, so where they overlap they are not cancelled out
+        let ((a, b), (c, d)) = (s.start, end);
+        let acc = self.accumulator();
+        acc.line(a, c);
+        acc.line(c, d);
+        acc.line(d, b);
+        acc.line(b, a);
+    }
+    
+    fn segment(&mut self, p0: T2<N, N>, p1: T2<N, N>, w: N) {
+        let (p0, p1) = match self.clip {
+            Some((min, max)) => match clip_line(min, max, p0, p1) {
+                Some(line) => line,
+                None => return
+            },
+            None if finite(p0) && finite(p1) => (p0, p1),
+            None => return
+        };
+        
+        let one = N::int(1);
+        let half = one / N::int(2);
+        let intensity = self.weight * w;
+        
+        if let Raster::Area(width) = self.raster {
+            self.area_segment(p0, p1, width, intensity);
+            return;
+        }
+        
+        let T2(mut x0, mut y0) = p0;
+        let T2(mut x1, mut y1) = p1;
+        
         // shortcut to draw pixels
-        let mut draw = |x, y, v| {
-            (self.draw)(T2(x, y), v);
+        let mut draw = |x, y, v: N| {
+            (self.draw)(T2(x, y), v * intensity);
         };
         
         // http://en.wikipedia.org/wiki/Xiaolin_Wu%27s_line_algorithm
@@ -80,12 +595,32 @@ impl<F> Pen<F> where F: FnMut(T2<isize, isize>, N)
         
         let dx = x1 - x0;
         let dy = y1 - y0;
-        let gradient = if dx > 0. {
+        let gradient = if dx > N::int(0) {
             dy / dx
         } else {
-            1.0
+            one
         };
         
+        // Both ends in the same column. The code below would draw it twice with a total
+        // coverage of 1 + dx instead of dx, which shows up as beads at the joints.
+        if round(x0) == round(x1) {
+            let ymid = half * (y0 + y1);
+            let xpxl: isize = round(x0).cast().unwrap();
+            let ypxl: isize = ipart(ymid).cast().unwrap();
+            
+            let a = dx * fpart(ymid);
+            let b = dx * rfpart(ymid);
+            
+            if steep {
+                draw(ypxl,   xpxl, b);
+                draw(ypxl+1, xpxl, a);
+            } else {
+                draw(xpxl, ypxl,   b);
+                draw(xpxl, ypxl+1, a);
+            }
+            return;
+        }
+        
         // handle first endpoint
         let xend = round(x0);
         let yend = y0 + gradient * (xend - x0);
@@ -132,23 +667,261 @@ impl<F> Pen<F> where F: FnMut(T2<isize, isize>, N)
                 let a = fpart(intery);
                 let py: isize = ipart(intery).cast().unwrap();
                 
-                draw(py,   x, 1.0 - a);
+                draw(py,   x, one - a);
                 draw(py+1, x, a);
-                intery += gradient;
+                intery = intery + gradient;
             }
         } else {
             for x in xpxl1 + 1 .. xpxl2 {
                 let a = fpart(intery);
                 let py: isize = ipart(intery).cast().unwrap();
                 
-                draw(x, py,   1.0 - a);
+                draw(x, py,   one - a);
                 draw(x, py+1, a);
-                intery += gradient;
+                intery = intery + gradient;
             }
         }
     }
 }
 
+#[test]
+fn test_clip_line() {
+    let clip = |p0: (f64, f64), p1: (f64, f64)| {
+        clip_line(T2(0.0, 0.0), T2(10.0, 10.0), T2(p0.0, p0.1), T2(p1.0, p1.1))
+            .map(|(a, b)| (a.0, a.1, b.0, b.1))
+    };
+    let close = |a: (f64, f64, f64, f64), b: (f64, f64, f64, f64)| {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9 && (a.2 - b.2).abs() < 1e-9 && (a.3 - b.3).abs() < 1e-9
+    };
+    
+    // outside, and outside past a corner
+    assert_eq!(clip((12.0, 3.0), (15.0, 8.0)), None);
+    assert_eq!(clip((8.0, -5.0), (15.0, 2.0)), None);
+    
+    // parallel to an edge, outside and inside
+    assert_eq!(clip((-2.0, 12.0), (8.0, 12.0)), None);
+    assert_eq!(clip((2.0, 3.0), (8.0, 3.0)), Some((2.0, 3.0, 8.0, 3.0)));
+    
+    // in through the left edge and out through the bottom one
+    let inside = clip((-2.0, 4.0), (8.0, 14.0)).expect("crosses the rectangle");
+    assert!(close(inside, (0.0, 6.0, 4.0, 10.0)), "{:?}", inside);
+    
+    // NaN and infinite ends
+    let (nan, inf) = (::std::f64::NAN, ::std::f64::INFINITY);
+    assert_eq!(clip((nan, 3.0), (8.0, 3.0)), None);
+    assert_eq!(clip((2.0, 3.0), (8.0, nan)), None);
+    assert_eq!(clip((2.0, 3.0), (inf, 3.0)), None);
+    
+    // and the pen skips them, with a clip rectangle or without
+    let mut drawn = 0;
+    {
+        let mut pen = Pen::new(|_, _: f64| drawn += 1);
+        pen.line(T2(2.0, 3.0), T2(nan, 5.0));
+        pen.set_clip(T2(0.0, 0.0), T2(10.0, 10.0));
+        pen.line(T2(2.0, 3.0), T2(nan, 5.0));
+    }
+    assert_eq!(drawn, 0);
+}
+
+#[test]
+fn test_polyline_coverage() {
+    // a polyline made of many short steps should deposit exactly its length
+    let mut total = 0.0;
+    {
+        let mut pen = Pen::new(|_, v| total += v);
+        pen.set_polyline(true);
+        pen.move_to(T2(0.3, 2.7));
+        for i in 1 ... 200 {
+            pen.line_to(T2(0.3 + 0.05 * i as f64, 2.7));
+        }
+        pen.flush();
+    }
+    assert!((total - 10.0f64).abs() < 1e-6, "total coverage {}", total);
+}
+
+#[test]
+fn test_weights() {
+    // a weighted line of length 10 and an unweighted one of length 4
+    let total = |weight: f64, w: f64| {
+        let mut total = 0.0;
+        {
+            let mut pen = Pen::new(|_, v| total += v);
+            pen.set_weight(weight);
+            pen.move_to(T2(2.0, 5.3));
+            pen.line_to_weighted(T2(12.0, 5.3), w);
+            pen.line_to(T2(12.0, 9.3));
+            pen.flush();
+        }
+        total
+    };
+    for &(weight, w) in &[(1.0, 1.0), (0.5, 1.0), (1.0, 3.0), (0.5, 3.0), (-2.0, 0.25)] {
+        let expected = weight * (10.0 * w + 4.0);
+        let t = total(weight, w);
+        assert!((t - expected).abs() < 1e-9, "weight {}, segment weight {}: {}", weight, w, t);
+    }
+}
+
+#[test]
+fn test_area_stroke() {
+    // the same coverage for strokes of the same length in any direction
+    let stroke = |p0: T2<f64, f64>, p1: T2<f64, f64>, weight: f64| {
+        let mut total = 0.0;
+        {
+            let mut pen = Pen::new(|_, v| total += v);
+            pen.set_clip(T2(0.0, 0.0), T2(20.0, 20.0));
+            pen.set_raster(Raster::Area(1.0));
+            pen.set_weight(weight);
+            pen.line(p0, p1);
+        }
+        total
+    };
+    let d = 10.0 / 2.0f64.sqrt();
+    let axis = stroke(T2(2.0, 5.3), T2(12.0, 5.3), 1.0);
+    let diagonal = stroke(T2(2.2, 3.1), T2(2.2 + d, 3.1 + d), 1.0);
+    assert!((axis - 10.0).abs() < 1e-9, "axis aligned {}", axis);
+    assert!((diagonal - 10.0).abs() < 1e-9, "diagonal {}", diagonal);
+    
+    // negative weights are drawn too
+    assert!((stroke(T2(2.0, 5.3), T2(12.0, 5.3), -1.0) + 10.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_area_joints() {
+    // pixels of a path through `points`, with the weight of the segment to each point
+    let path = |points: &[(f64, f64, f64)]| {
+        let mut pixels = Vec::new();
+        {
+            let mut pen = Pen::new(|p, v| pixels.push((p, v)));
+            pen.set_clip(T2(0.0, 0.0), T2(20.0, 20.0));
+            pen.set_raster(Raster::Area(1.0));
+            pen.move_to(T2(points[0].0, points[0].1));
+            for &(x, y, w) in &points[1 ..] {
+                pen.line_to_weighted(T2(x, y), w);
+            }
+            pen.flush();
+        }
+        pixels
+    };
+    let total = |pixels: &[(T2<isize, isize>, f64)]| pixels.iter().map(|&(_, v)| v).sum::<f64>();
+    
+    // the joint is covered once: the total is the length, and no pixel is covered more than once
+    let bent = path(&[(2.2, 3.1, 0.0), (10.2, 3.1, 1.0), (15.2, 8.1, 1.0)]);
+    let length = 8.0 + 50.0f64.sqrt();
+    assert!((total(&bent) - length).abs() < 1e-9, "bent {}", total(&bent));
+    let mut coverage = ::std::collections::HashMap::new();
+    for &(p, v) in &bent {
+        *coverage.entry((p.0, p.1)).or_insert(0.0) += v;
+    }
+    assert!(coverage.values().all(|&v| v <= 1.0 + 1e-9));
+    
+    // each segment with its own weight
+    let mixed = path(&[(2.0, 5.5, 0.0), (8.0, 5.5, 1.0), (12.0, 5.5, 3.0)]);
+    assert!((total(&mixed) - (6.0 + 4.0 * 3.0)).abs() < 1e-9, "mixed {}", total(&mixed));
+}
+
+#[test]
+fn test_dash_phase() {
+    // coverage of the pixels in rows 0 .. 20, of lines from x = 1 to 11 through the pieces ending at xs
+    let draw = |rows: &[f64], xs: &[f64]| {
+        let mut grid = [[0.0f64; 20]; 20];
+        {
+            let mut pen = Pen::new(|p: T2<isize, isize>, v| {
+                if p.0 >= 0 && p.0 < 20 && p.1 >= 0 && p.1 < 20 {
+                    grid[p.1 as usize][p.0 as usize] += v;
+                }
+            });
+            pen.set_clip(T2(0.0, 0.0), T2(20.0, 20.0));
+            pen.set_raster(Raster::Area(1.0));
+            pen.set_dash(&[2.0, 1.0], 0.5);
+            for &y in rows {
+                pen.move_to(T2(1.0, y));
+                for &x in xs {
+                    pen.line_to(T2(x, y));
+                }
+            }
+            pen.flush();
+        }
+        grid
+    };
+    let same = |a: &[f64; 20], b: &[f64; 20]| a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-9);
+    
+    // the pattern continues from one segment to the next
+    let whole = draw(&[5.5], &[11.0]);
+    let pieces = draw(&[5.5], &[1.7, 2.4, 4.9, 5.3, 8.0, 11.0]);
+    assert!(same(&whole[5], &pieces[5]), "{:?} != {:?}", whole[5], pieces[5]);
+    assert!(whole[5].iter().sum::<f64>() < 9.0);
+    
+    // and starts over with each subpath
+    let twice = draw(&[5.5, 12.5], &[1.7, 2.4, 4.9, 5.3, 8.0, 11.0]);
+    assert!(same(&twice[5], &twice[12]), "{:?} != {:?}", twice[5], twice[12]);
+}
+
+#[cfg(test)]
+fn rasterize<D>(draw: D) -> [[f64; 20]; 20] where D: FnOnce(&mut Pen<&mut FnMut(T2<isize, isize>, f64), f64>) {
+    let mut grid = [[0.0f64; 20]; 20];
+    {
+        let mut plot = |p: T2<isize, isize>, v: f64| {
+            if p.0 >= 0 && p.0 < 20 && p.1 >= 0 && p.1 < 20 {
+                grid[p.1 as usize][p.0 as usize] += v;
+            }
+        };
+        let mut pen: Pen<&mut FnMut(T2<isize, isize>, f64), f64> = Pen::new(&mut plot);
+        pen.set_clip(T2(0.0, 0.0), T2(20.0, 20.0));
+        pen.set_raster(Raster::Area(1.0));
+        draw(&mut pen);
+        pen.flush();
+    }
+    grid
+}
+
+#[test]
+fn test_transform_stack() {
+    use std::f64::consts::FRAC_PI_2;
+    let same = |a: [[f64; 20]; 20], b: [[f64; 20]; 20]| {
+        a.iter().zip(b.iter()).all(|(a, b)| a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-9))
+    };
+    
+    // the inner transform is applied first: rotate, then translate
+    let stacked = rasterize(|pen| {
+        pen.push_transform(Transform::translate(5.0, 3.0));
+        pen.push_transform(Transform::rotate(FRAC_PI_2));
+        pen.line(T2(1.0, 1.0), T2(1.0, 5.0));
+    });
+    assert!(same(stacked, rasterize(|pen| pen.line(T2(4.0, 4.0), T2(0.0, 4.0)))));
+    
+    // pop_transform restores the transform from before
+    let popped = rasterize(|pen| {
+        pen.push_transform(Transform::translate(5.0, 3.0));
+        pen.push_transform(Transform::rotate(FRAC_PI_2));
+        pen.pop_transform();
+        pen.line(T2(1.0, 1.0), T2(1.0, 5.0));
+        pen.pop_transform();
+        pen.line(T2(10.0, 1.0), T2(10.0, 5.0));
+        assert_eq!(pen.transform(), Transform::identity());
+    });
+    let expected = rasterize(|pen| {
+        pen.line(T2(6.0, 4.0), T2(6.0, 8.0));
+        pen.line(T2(10.0, 1.0), T2(10.0, 5.0));
+    });
+    assert!(same(popped, expected));
+    
+    // fills and curves go through the stack too
+    let m = Transform::skew(0.3, 0.0).then(&Transform::rotate(0.5)).then(&Transform::translate(8.0, 2.0));
+    let square = [T2(1.0, 1.0), T2(6.0, 1.0), T2(6.0, 6.0), T2(1.0, 6.0)];
+    let shapes = |pen: &mut Pen<&mut FnMut(T2<isize, isize>, f64), f64>, m: &Transform<f64>| {
+        pen.fill(&[square.iter().map(|&p| m.apply(p)).collect::<Vec<_>>()]);
+        pen.move_to(m.apply(T2(1.0, 8.0)));
+        pen.quad_to(m.apply(T2(4.0, 14.0)), m.apply(T2(7.0, 8.0)));
+        pen.cubic_to(m.apply(T2(8.0, 2.0)), m.apply(T2(10.0, 14.0)), m.apply(T2(11.0, 8.0)));
+    };
+    let mapped = rasterize(|pen| {
+        pen.push_transform(m);
+        shapes(pen, &Transform::identity());
+    });
+    assert!(same(mapped, rasterize(|pen| shapes(pen, &m))));
+    assert!(mapped.iter().map(|row| row.iter().sum::<f64>()).sum::<f64>() > 25.0);
+}
+
 /*
 
 impl<Draw> Pen<Draw> where Draw: FnMut(Vector2<i32>, u32)
@@ -1,18 +1,168 @@
 use canvas::{Canvas, Meta, Data, Initial};
 use std::ops::{Range};
+use std::cmp;
 use tuple::T2;
 use math::prelude::*;
-use contour::ContourPlot;
-use pen::Pen;
+use contour::{ContourPlot, ContourStats, Polyline, NoBound, Bound};
+use interval::Interval;
+use sampler::{Sampler, BATCH};
+use pen::{Pen, Raster};
+use transform::Transform;
+use marker::Marker;
+use label::{self, Labels};
+use svg::{self, PathError};
+use trace::Tracer;
+use dual::Dual;
+use std::fmt::Display;
 use colormap;
 use image::GrayImage;
 use array::{Array, RowMajor};
 use rng::{VRng, DefaultRng};
 
+/// Resolution of a contour plot. `None` picks a value from the size of the canvas.
+#[derive(Copy, Clone, Debug)]
+pub struct ContourOptions<N = f32> {
+    /// depth down to which the quadtree is always subdivided, default: half the plot depth
+    pub search_depth: Option<u8>,
+    /// depth of the cells that are drawn, default: cells of one pixel
+    pub plot_depth: Option<u8>,
+    /// locate the crossings on the cell edges to this distance (in pixels) by calling the function again,
+    /// default: linear interpolation of the corner values
+    pub tolerance: Option<N>,
+    /// evaluate the function only once per grid point, for expensive functions
+    pub memoize: bool,
+    /// rasterizer of the lines, default: `Raster::Wu`.
+    /// `Raster::Area` gives the same line weight in all directions.
+    pub raster: Raster<N>
+}
+impl<N> Default for ContourOptions<N> {
+    fn default() -> ContourOptions<N> {
+        ContourOptions {
+            search_depth: None,
+            plot_depth: None,
+            tolerance: None,
+            memoize: false,
+            raster: Raster::Wu
+        }
+    }
+}
+
+/// Which samples of `contour_gradient` are drawn, by the residual `|f(p)|` after the Newton iterations.
+#[derive(Copy, Clone, Debug)]
+pub enum Acceptance<N = f32> {
+    /// every sample, converged or not
+    All,
+    /// only the samples with a residual up to this
+    Threshold(N),
+    /// the samples with a residual below this, weighted by `1 - residual / tolerance`
+    Weighted(N)
+}
+
+/// Samples of the last `contour_gradient` plot.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GradientStats {
+    pub samples: usize,
+    /// samples that were drawn
+    pub accepted: usize,
+    /// samples that `Acceptance` allowed, but that ended off the canvas (or at NaN)
+    pub off_canvas: usize
+}
+
+// The root cells of a contour plot are squares of a power of two pixels, covering the shorter side.
+// Returns their size, the plot depth and the search depth.
+fn contour_grid<N>(w: usize, h: usize, options: &ContourOptions<N>) -> (usize, u8, u8) {
+    let tile_depth = cmp::min(w, h).next_power_of_two().trailing_zeros() as u8;
+    let plot_depth = options.plot_depth.unwrap_or(tile_depth);
+    let search_depth = cmp::min(options.search_depth.unwrap_or(plot_depth / 2), plot_depth);
+    (1 << tile_depth, plot_depth, search_depth)
+}
+
+// a function of figure coordinates, called with grid coordinates
+struct Mapped<'a, S: 'a, N> {
+    func:   &'a S,
+    scale:  T2<N, N>,
+    offset: T2<N, N>
+}
+impl<'a, S, N> Sampler<N> for Mapped<'a, S, N> where S: Sampler<N>, N: Real {
+    fn sample(&self, p: T2<N, N>) -> N {
+        self.func.sample(p * self.scale + self.offset)
+    }
+    fn sample_batch(&self, points: &[T2<N, N>], out: &mut [N]) {
+        let mut mapped = [T2(N::int(0), N::int(0)); BATCH];
+        for (points, out) in points.chunks(BATCH).zip(out.chunks_mut(BATCH)) {
+            for (m, &p) in mapped.iter_mut().zip(points) {
+                *m = p * self.scale + self.offset;
+            }
+            self.func.sample_batch(&mapped[.. points.len()], out);
+        }
+    }
+}
+impl<'a, B, N> Bound<N> for Mapped<'a, B, N> where B: Bound<N>, N: Real + PartialOrd {
+    fn bound(&self, p: T2<Interval<N>, Interval<N>>) -> Interval<N> {
+        let T2(x, y) = p;
+        self.func.bound(T2(x * self.scale.0 + self.offset.0, y * self.scale.1 + self.offset.1))
+    }
+}
+
+// runs a contour plot of the figure, on this thread or on several
+trait Run<F, B, N> {
+    fn run<'a, D>(&self, c: &mut ContourPlot<Mapped<'a, F, N>, D, N, Mapped<'a, B, N>>)
+        where D: FnMut(T2<isize, isize>, N), F: 'a, B: 'a;
+}
+struct Serial;
+impl<F, B, N> Run<F, B, N> for Serial
+    where F: Sampler<N>, B: Bound<N>,
+          N: Real<Bool=bool> + PartialOrd + Cast<isize> + Cast<usize>,
+          usize: Cast<N>, isize: Cast<N>
+{
+    fn run<'a, D>(&self, c: &mut ContourPlot<Mapped<'a, F, N>, D, N, Mapped<'a, B, N>>)
+        where D: FnMut(T2<isize, isize>, N), F: 'a, B: 'a
+    {
+        c.run()
+    }
+}
+struct Threads(usize);
+impl<F, B, N> Run<F, B, N> for Threads
+    where F: Sampler<N> + Sync, B: Bound<N> + Sync,
+          N: Real<Bool=bool> + PartialOrd + Cast<isize> + Cast<usize> + Send + Sync,
+          usize: Cast<N>, isize: Cast<N>
+{
+    fn run<'a, D>(&self, c: &mut ContourPlot<Mapped<'a, F, N>, D, N, Mapped<'a, B, N>>)
+        where D: FnMut(T2<isize, isize>, N), F: 'a, B: 'a
+    {
+        c.run_parallel(self.0)
+    }
+}
+
+// A pen adding to the pixels of the canvas, in polyline mode. It is clipped to the canvas
+// and one pixel of margin, as the line algorithm touches the neighbouring pixels.
+fn canvas_pen<'a, M, D, N>(meta: &'a M, data: &'a mut D) -> Pen<impl FnMut(T2<isize, isize>, N) + 'a, N>
+    where M: Meta, D: Data,
+          N: Real + PartialOrd + Cast<isize> + Cast<usize> + Cast<D::Item>,
+          usize: Cast<N>, isize: Cast<N>
+{
+    let (w, h) = meta.size();
+    let start: T2<usize, usize> = T2(0, 0);
+    let end: T2<usize, usize> = T2(w-1, h-1);
+
+    let mut pen = Pen::new(move |p: T2<isize, isize>, v: N| {
+        let op: Option<T2<usize, usize>> = p.cast_clipped(start ... end);
+        if let Some(T2(x, y)) = op {
+            let pixel = data.get_mut(meta.index((x, y)));
+            *pixel += v.cast().unwrap();
+        }
+    });
+    pen.set_clip(T2(N::int(-1), N::int(-1)), T2(w, h).cast().unwrap());
+    pen.set_polyline(true);
+    pen
+}
+
 pub struct Figure<N: Real = f32, C: Canvas = Array<Vec<f32>, RowMajor>> {
     offset: T2<N, N>,
     size:   T2<N, N>,
-    canvas: C
+    canvas: C,
+    contour_stats: ContourStats,
+    gradient_stats: GradientStats
 }
 
 impl<N, C> Figure<N, C>
@@ -30,7 +180,9 @@ impl<N, C> Figure<N, C>
             canvas: C::new(
                 C::Meta::new(width, height),
                 C::Data::initial(width, height)
-            )
+            ),
+            contour_stats: ContourStats::default(),
+            gradient_stats: GradientStats::default()
         }
     }
 
@@ -106,81 +258,432 @@ impl<N, C> Figure<N, C>
     }
 
     pub fn contour<F>(&mut self, func: F) -> &mut Self
-        where F: Fn(T2<N, N>) -> N,
-              N: Real<Bool=bool> + Cast<f32>,
-              f32: Cast<<C::Data as Data>::Item>
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        self.contour_with(func, &ContourOptions::default())
+    }
+
+    pub fn contour_with<F>(&mut self, func: F, options: &ContourOptions<N>) -> &mut Self
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        self.contour_levels_with(func, &[N::int(0)], None, options)
+    }
+
+    /// Draw the contours of all `levels` in one pass, evaluating `func` only once per point.
+    /// Each level can have its own weight.
+    pub fn contour_levels<F>(&mut self, func: F, levels: &[N], weights: Option<&[N]>) -> &mut Self
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        self.contour_levels_with(func, levels, weights, &ContourOptions::default())
+    }
+
+    pub fn contour_levels_with<F>(&mut self, func: F, levels: &[N], weights: Option<&[N]>, options: &ContourOptions<N>) -> &mut Self
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        let levels: Vec<(N, N)> = match weights {
+            Some(weights) => {
+                assert_eq!(levels.len(), weights.len());
+                levels.iter().cloned().zip(weights.iter().cloned()).collect()
+            },
+            None => levels.iter().map(|&l| (l, N::int(1))).collect()
+        };
+        self.run_contour(func, None::<NoBound<N>>, levels, None, options, Serial)
+    }
+
+    /// Like `contour_levels_with`, but cells that may contain a contour according to `bound`
+    /// are subdivided down to the plot depth, so small closed contours are not missed.
+    /// `bound` has to contain all values of `func` over the given rectangle.
+    /// A function generic over `Real` can bound itself, as `Interval` is a `Real`.
+    pub fn contour_bounded<F, B>(&mut self, func: F, bound: B, levels: &[N], options: &ContourOptions<N>) -> &mut Self
+        where F: Sampler<N>,
+              B: Bound<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        let levels = levels.iter().map(|&l| (l, N::int(1))).collect();
+        self.run_contour(func, Some(bound), levels, None, options, Serial)
+    }
+
+    /// Fill the bands between consecutive `levels` (in increasing order), like a filled contour plot.
+    /// The band below the first level gets `values[0]`, the next one `values[1]` and so on.
+    /// Without `values`, the index of the band is used.
+    pub fn contour_bands<F>(&mut self, func: F, levels: &[N], values: Option<&[N]>) -> &mut Self
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        self.contour_bands_with(func, levels, values, &ContourOptions::default())
+    }
+
+    pub fn contour_bands_with<F>(&mut self, func: F, levels: &[N], values: Option<&[N]>, options: &ContourOptions<N>) -> &mut Self
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        assert!(levels.windows(2).all(|w| w[0] < w[1]), "levels have to be increasing");
+        let values: Vec<N> = match values {
+            Some(values) => {
+                assert_eq!(values.len(), levels.len() + 1);
+                values.to_vec()
+            },
+            None => (0 ... levels.len()).map(|i| i.cast().unwrap()).collect()
+        };
+        let levels = levels.iter().map(|&l| (l, N::int(1))).collect();
+        self.run_contour(func, None::<NoBound<N>>, levels, Some(values), options, Serial)
+    }
+
+    /// Draw the `level` contour of the samples in `array`, which cover the whole figure.
+    /// Values between the samples are interpolated bilinearly.
+    pub fn contour_array<D, M>(&mut self, array: &Array<D, M>, level: N) -> &mut Self
+        where D: Data, M: Meta, D::Item: Copy + Cast<N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        let (w, h) = array.meta.size();
+        assert!(w > 1 && h > 1, "need at least 2x2 samples");
+        
+        let offset = self.offset;
+        let last: T2<N, N> = T2(w - 1, h - 1).cast().unwrap();
+        let scale = last / self.size;
+        let zero = N::int(0);
+        let clamp = |v: N, hi: N| if v < zero { zero } else if v > hi { hi } else { v };
+        
+        self.contour_levels(move |p: T2<N, N>| {
+            // position in samples
+            let T2(u, v) = (p - offset) * scale;
+            let (u, v) = (clamp(u, last.0), clamp(v, last.1));
+            let x: usize = cmp::min(u.floor().cast().unwrap(), w - 2);
+            let y: usize = cmp::min(v.floor().cast().unwrap(), h - 2);
+            let T2(x0, y0): T2<N, N> = T2(x, y).cast().unwrap();
+            let (fx, fy) = (u - x0, v - y0);
+            
+            let f = |x: usize, y: usize| -> N { array[(x, y)].cast().unwrap() };
+            let bottom = f(x, y) + (f(x+1, y) - f(x, y)) * fx;
+            let top = f(x, y+1) + (f(x+1, y+1) - f(x, y+1)) * fx;
+            bottom + (top - bottom) * fy
+        }, &[level], None)
+    }
+
+    /// Like `contour_levels_with`, with the cells of the top levels of the quadtrees distributed over `threads` threads.
+    /// The result is the same as drawn by one thread.
+    pub fn contour_parallel<F>(&mut self, func: F, levels: &[N], threads: usize, options: &ContourOptions<N>) -> &mut Self
+        where F: Sampler<N> + Sync,
+              N: Real<Bool=bool> + PartialOrd + Send + Sync + Cast<<C::Data as Data>::Item>
+    {
+        let levels = levels.iter().map(|&l| (l, N::int(1))).collect();
+        self.run_contour(func, None::<NoBound<N>>, levels, None, options, Threads(threads))
+    }
+
+    fn run_contour<F, B, R>(&mut self, func: F, bound: Option<B>, levels: Vec<(N, N)>, bands: Option<Vec<N>>,
+                            options: &ContourOptions<N>, runner: R) -> &mut Self
+        where F: Sampler<N>,
+              B: Bound<N>,
+              R: Run<F, B, N>,
+              N: Real<Bool=bool> + PartialOrd + Cast<<C::Data as Data>::Item>
     {
         let size = self.size;
         let offset = self.offset;
         
-        self.canvas.run_mut(|meta, data| {
+        self.contour_stats = self.canvas.run_mut(|meta, data| {
             let (subpixel_width, subpixel_height) = meta.subpixel_size();
             let subpixel_size: T2<N, N> = T2(subpixel_width, subpixel_height).cast().unwrap();
 
             let (w, h) = meta.size();
-            let n = 9; //(w as f32).log(2.0).ceil() as u8;
             let scale_inv = size / subpixel_size;
 
-            let start: T2<usize, usize> = T2(0, 0);
-            let end: T2<usize, usize> = T2(w-1, h-1);
+            let (tile, plot_depth, search_depth) = contour_grid(w, h, options);
+
+            // size of a grid step in pixels
+            let tile_size: N = tile.cast().unwrap();
+            let cells: N = (1usize << plot_depth).cast().unwrap();
+            let step = tile_size / cells;
+
+            let scale = scale_inv * step;
             
-            let mut c = ContourPlot {
-                plot_depth: n,
-                search_depth: n/2,
-                func: |p: T2<usize, usize>| {
-                    let p: T2<N, N> = p.cast().unwrap();
-                    func(p * scale_inv + offset)
-                },
-                pen: Pen::new(|p: T2<isize, isize>, v| {
-                    let op: Option<T2<usize, usize>> = p.cast_clipped(start ... end);
-                    if let Some(T2(x, y)) = op {
-                        let pixel = data.get_mut(meta.index((x, y)));
-                        *pixel += v.cast().unwrap();
+            let c = ContourPlot::new(
+                Mapped { func: &func, scale: scale, offset: offset },
+                canvas_pen(meta, data),
+                plot_depth
+            );
+            let mut c = c.with_bound(bound.as_ref().map(|bound| Mapped { func: bound, scale: scale, offset: offset }));
+            c.search_depth = search_depth;
+            c.tiles = ((w + tile - 1) / tile, (h + tile - 1) / tile);
+            c.levels = levels;
+            c.bands = bands;
+            c.tolerance = options.tolerance.map(|t| t / step);
+            c.memoize = options.memoize;
+
+            c.pen.set_raster(options.raster);
+            c.pen.push_transform(Transform::scale(step, step));
+            runner.run(&mut c);
+            c.stats()
+        });
+        
+        self
+    }
+
+    /// Function evaluations of the last contour plot drawn.
+    pub fn contour_stats(&self) -> ContourStats {
+        self.contour_stats
+    }
+
+    /// The contours of `levels` as lines in figure coordinates, without drawing them.
+    pub fn contour_polylines<F>(&self, func: F, levels: &[N]) -> Vec<Polyline<N>>
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd
+    {
+        self.contour_polylines_with(func, levels, &ContourOptions::default())
+    }
+
+    pub fn contour_polylines_with<F>(&self, func: F, levels: &[N], options: &ContourOptions<N>) -> Vec<Polyline<N>>
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd
+    {
+        let size = self.size;
+        let offset = self.offset;
+
+        self.canvas.run(|meta, _| {
+            let (subpixel_width, subpixel_height) = meta.subpixel_size();
+            let subpixel_size: T2<N, N> = T2(subpixel_width, subpixel_height).cast().unwrap();
+
+            let (w, h) = meta.size();
+            let (tile, plot_depth, search_depth) = contour_grid(w, h, options);
+
+            // figure units per grid step
+            let tile_size: N = tile.cast().unwrap();
+            let cells: N = (1usize << plot_depth).cast().unwrap();
+            let pixels = tile_size / cells;
+            let step = size / subpixel_size * pixels;
+
+            let mut c = ContourPlot::new(
+                Mapped { func: &func, scale: step, offset: offset },
+                Pen::new(|_: T2<isize, isize>, _: N| ()),
+                plot_depth
+            );
+            c.search_depth = search_depth;
+            c.tiles = ((w + tile - 1) / tile, (h + tile - 1) / tile);
+            c.levels = levels.iter().map(|&l| (l, N::int(1))).collect();
+            c.tolerance = options.tolerance.map(|t| t / pixels);
+            c.memoize = options.memoize;
+
+            let mut lines = c.polylines();
+            for line in &mut lines {
+                for p in &mut line.points {
+                    *p = *p * step + offset;
+                }
+            }
+            lines
+        })
+    }
+
+    /// Draw the contours of `levels`, with their values written along them.
+    pub fn contour_labeled<F>(&mut self, func: F, levels: &[N], labels: &Labels) -> &mut Self
+        where F: Sampler<N>,
+              N: Real<Bool=bool> + PartialOrd + Display + Cast<f32> + Cast<<C::Data as Data>::Item>,
+              f32: Cast<N>
+    {
+        let lines = self.contour_polylines(func, levels);
+        let size = self.size;
+        let offset = self.offset;
+
+        self.canvas.run_mut(|meta, data| {
+            let (subpixel_width, subpixel_height) = meta.subpixel_size();
+            let subpixel_size: T2<N, N> = T2(subpixel_width, subpixel_height).cast().unwrap();
+            let canvas_scale: T2<N, N> = subpixel_size / size;
+
+            let mut pen = canvas_pen(meta, data);
+
+            for line in lines {
+                let text = format!("{}", levels[line.level]);
+                let points: Vec<T2<f32, f32>> = line.points.iter()
+                    .map(|&p| ((p - offset) * canvas_scale).cast().unwrap())
+                    .collect();
+                let placements = labels.place(&points, line.closed, labels.width(&text));
+                let gaps: Vec<_> = placements.iter().map(|p| p.gap).collect();
+
+                for piece in label::cut(&points, line.closed, &gaps) {
+                    let mut piece = piece.into_iter().map(|p| -> T2<N, N> { p.cast().unwrap() });
+                    if let Some(p) = piece.next() {
+                        pen.move_to(p);
+                        for p in piece {
+                            pen.line_to(p);
+                        }
                     }
-                })
+                }
+                for placement in &placements {
+                    labels.draw(&mut pen, &text, placement);
+                }
+            }
+            pen.flush();
+        });
+
+        self
+    }
+
+    /// Draw `marker` at every point.
+    pub fn scatter<I>(&mut self, points: I, marker: &Marker) -> &mut Self
+        where I: IntoIterator<Item=T2<N, N>>,
+              N: PartialOrd + Cast<<C::Data as Data>::Item>,
+              f32: Cast<N>
+    {
+        let size = self.size;
+        let offset = self.offset;
+
+        self.canvas.run_mut(|meta, data| {
+            let (subpixel_width, subpixel_height) = meta.subpixel_size();
+            let subpixel_size: T2<N, N> = T2(subpixel_width, subpixel_height).cast().unwrap();
+            let canvas_scale: T2<N, N> = subpixel_size / size;
+
+            let mut pen = canvas_pen(meta, data);
+
+            // NaN and infinite points are left out, like by `sample`
+            for p in points {
+                let T2(x, y) = (p - offset) * canvas_scale;
+                if x - x == N::int(0) && y - y == N::int(0) {
+                    marker.draw(&mut pen, T2(x, y));
+                }
+            }
+            pen.flush();
+        });
+
+        self
+    }
+
+    /// Draw the SVG path data `d`, with `transform` from the path to figure coordinates.
+    pub fn path(&mut self, d: &str, transform: &Transform<N>) -> Result<&mut Self, PathError>
+        where N: PartialOrd + Cast<<C::Data as Data>::Item>,
+              f64: Cast<N>
+    {
+        let size = self.size;
+        let offset = self.offset;
+
+        self.canvas.run_mut(|meta, data| {
+            let (subpixel_width, subpixel_height) = meta.subpixel_size();
+            let subpixel_size: T2<N, N> = T2(subpixel_width, subpixel_height).cast().unwrap();
+            let canvas_scale: T2<N, N> = subpixel_size / size;
+
+            let mut pen = canvas_pen(meta, data);
+            pen.push_transform(Transform::translate(N::int(0) - offset.0, N::int(0) - offset.1)
+                .then(&Transform::scale(canvas_scale.0, canvas_scale.1)));
+            svg::draw_path(&mut pen, d, transform)
+        })?;
+
+        Ok(self)
+    }
+
+    /// Draw the zero set of `func` by following it with `tracer`.
+    /// Like for `contour_gradient`, `func` returns the value and the gradient divided by its squared length.
+    pub fn contour_trace<F>(&mut self, func: F, tracer: &Tracer<N>) -> &mut Self
+        where F: Fn(T2<N, N>) -> (N, T2<N, N>),
+              N: PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        let size = self.size;
+        let offset = self.offset;
+
+        self.canvas.run_mut(|meta, data| {
+            let (subpixel_width, subpixel_height) = meta.subpixel_size();
+            let subpixel_size: T2<N, N> = T2(subpixel_width, subpixel_height).cast().unwrap();
+            let scale: T2<N, N> = size / subpixel_size;
+
+            let (w, h) = meta.size();
+            let max: T2<N, N> = T2(w, h).cast().unwrap();
+            let mut pen = canvas_pen(meta, data);
+
+            // the same in pixels: the gradient scales with the size of a pixel
+            let pixel_func = |p: T2<N, N>| {
+                let (v, grad_inv) = func(p * scale + offset);
+                let T2(x, y) = grad_inv;
+                let grad = grad_inv * (N::int(1) / (x * x + y * y)) * scale;
+                let T2(x, y) = grad;
+                (v, grad * (N::int(1) / (x * x + y * y)))
             };
-            c.run();
+            tracer.trace(pixel_func, &mut pen, T2(N::int(0), N::int(0)), max);
         });
-        
+
         self
     }
 
+    /// Newton iterations towards the zero set of `func` from random points, drawing where they end.
+    /// `func` returns the value and `gradient / |gradient|²`.
     #[inline]
     pub fn contour_gradient<F, V, R>(&mut self, func: F, samples: usize, iterations: usize, rng: &mut R) -> &mut Self
-        where F: Fn(T2<V, V>) -> (V, T2<V, V>), V: Real<Scalar=N>, R: VRng<T2<V, V>>
+        where F: Fn(T2<V, V>) -> (V, T2<V, V>), V: Real<Scalar=N>, R: VRng<T2<V, V>>,
+              N: PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        self.contour_gradient_with(func, samples, iterations, Acceptance::All, rng)
+    }
+
+    /// Like `contour_gradient`, drawing only the samples that `acceptance` allows.
+    pub fn contour_gradient_with<F, V, R>(&mut self, func: F, samples: usize, iterations: usize,
+                                          acceptance: Acceptance<N>, rng: &mut R) -> &mut Self
+        where F: Fn(T2<V, V>) -> (V, T2<V, V>), V: Real<Scalar=N>, R: VRng<T2<V, V>>,
+              N: PartialOrd + Cast<<C::Data as Data>::Item>
     {
         let size = self.size;
         let offset = self.offset;
 
-        self.canvas.run_mut(|meta, data| {
+        self.gradient_stats = self.canvas.run_mut(|meta, data| {
             let (subpixel_width, subpixel_height) = meta.subpixel_size();
             let subpixel_size: T2<N, N> = T2(subpixel_width, subpixel_height).cast().unwrap();
             let canvas_scale: T2<N, N> = subpixel_size / size;
+            let mut stats = GradientStats::default();
 
-            for sample_nr in 0 .. samples {
+            for _ in 0 .. samples {
                 let r: T2<V, V> = rng.next();
                 let mut p = r * size.map(Real::splat) + offset.map(Real::splat); // random point on the canvas
                 for _ in 0 .. iterations {
-//                    let r: T2<V, V> = rng.next();
                     let (q, grad_inv) = func(p);
-                    p = p - grad_inv * q; // * T2::uniform01(rng);
+                    p = p - grad_inv * q;
                 }
+                let residual = match acceptance {
+                    Acceptance::All => V::int(0),
+                    _ => func(p).0.abs()
+                };
                 
                 let r: T2<V, V> = rng.next();
                 let s: T2<V, V> = (p - offset.map(Real::splat)) * canvas_scale.map(Real::splat) + r;
 
-                for e in s.map(Real::values) {
+                for (e, residual) in s.map(Real::values).zip(residual.values()) {
                     let e: T2<N, N> = e;
+                    stats.samples += 1;
+                    // NaN residuals are rejected by the comparisons
+                    let weight: N = match acceptance {
+                        Acceptance::All => N::int(1),
+                        Acceptance::Threshold(tolerance) if residual <= tolerance => N::int(1),
+                        Acceptance::Weighted(tolerance) if residual < tolerance => N::int(1) - residual / tolerance,
+                        _ => continue
+                    };
                     if let Some(T2(x, y)) = e.cast_clipped(T2(0, 0) ... T2(subpixel_width-1, subpixel_height-1)) {
-                        *data.get_mut(meta.index((x, y))) += 1u8.cast().unwrap();
+                        *data.get_mut(meta.index((x, y))) += weight.cast().unwrap();
+                        stats.accepted += 1;
+                    } else {
+                        stats.off_canvas += 1;
                     }
                 }
             }
+            stats
         });
 
         self
     }
 
+    /// Samples of the last `contour_gradient` plot.
+    pub fn gradient_stats(&self) -> GradientStats {
+        self.gradient_stats
+    }
+
+    /// Like `contour_gradient`, but `func` only returns the value.
+    /// It is called with `Dual` coordinates, from which the gradient and the Newton step follow.
+    pub fn contour_gradient_auto<F, V, R>(&mut self, func: F, samples: usize, iterations: usize, rng: &mut R) -> &mut Self
+        where F: Fn(T2<Dual<V>, Dual<V>>) -> Dual<V>, V: Real<Scalar=N>, R: VRng<T2<V, V>>,
+              N: PartialOrd + Cast<<C::Data as Data>::Item>
+    {
+        self.contour_gradient(|p| {
+            let d = func(Dual::variables(p));
+            (d.value, d.grad_inv())
+        }, samples, iterations, rng)
+    }
+
     pub fn grayscale(&self, max: Option<<C::Data as Data>::Item>) -> GrayImage
         where <C::Data as Data>::Item: Real<Bool=bool> + Copy + Cast<usize>,
     usize: Cast<<C::Data as Data>::Item>
@@ -189,3 +692,158 @@ impl<N, C> Figure<N, C>
     }
 
 }
+
+#[test]
+fn test_contour_array() {
+    // the top two rows of the image are bright
+    let image = GrayImage::from_fn(8, 8, |_, y| ::image::Luma([if y < 2 { 255 } else { 0 }]));
+    let array = Array::from_image(image);
+    let mut fig: Figure<f32, Array<Vec<f32>, RowMajor>> = Figure::new(0.0 .. 1.0, 0.0 .. 1.0, (64, 64));
+    fig.contour_array(&array, 127.5);
+
+    // between the samples at y = 5 and 6, of 7
+    let y = 5.5 / 7.0 * 64.0;
+    let canvas = fig.canvas();
+    let mut drawn = 0;
+    for py in 0 .. 64 {
+        for px in 0 .. 64 {
+            if canvas[(px, py)] > 0.0 {
+                assert!((py as f32 - y).abs() < 3.0, "pixel at y = {}", py);
+                drawn += 1;
+            }
+        }
+    }
+    assert!(drawn >= 60);
+}
+
+#[test]
+fn test_contour_bands() {
+    // bands of f = x, in a row in the middle of the canvas
+    let row = |values: Option<&[f32]>| {
+        let mut fig: Figure<f32, Array<Vec<f32>, RowMajor>> = Figure::new(0.0 .. 1.0, 0.0 .. 1.0, (64, 64));
+        fig.contour_bands(|T2(x, _): T2<f32, f32>| x, &[0.25, 0.5, 0.75], values);
+        let canvas = fig.canvas();
+        (0 .. 64).map(|x| canvas[(x, 32)]).collect::<Vec<f32>>()
+    };
+    let check = |row: Vec<f32>, values: [f32; 4]| {
+        // pixel x covers x - 0.5 ... x + 0.5, and the bands change at 16, 32 and 48
+        for x in 1 .. 63 {
+            let expected = match x {
+                16 | 32 | 48 => 0.5 * (values[x / 16 - 1] + values[x / 16]),
+                _ => values[x / 16]
+            };
+            assert!((row[x] - expected).abs() < 1e-4, "pixel {}: {} instead of {}", x, row[x], expected);
+        }
+    };
+    check(row(Some(&[1.0, 2.0, 4.0, 8.0][..])), [1.0, 2.0, 4.0, 8.0]);
+    
+    // the index of the band
+    check(row(None), [0.0, 1.0, 2.0, 3.0]);
+}
+
+#[test]
+fn test_scatter() {
+    let mut fig: Figure<f32, Array<Vec<f32>, RowMajor>> = Figure::new(0.0 .. 1.0, 0.0 .. 1.0, (32, 32));
+    let nan = ::std::f32::NAN;
+    fig.scatter(vec![T2(0.5, 0.25), T2(nan, 0.5), T2(0.5, ::std::f32::INFINITY), T2(1e30, 0.5)],
+                &Marker::Bitmap { width: 1, height: 1, data: vec![1.0] });
+    
+    // only the finite point within the figure
+    let canvas = fig.canvas();
+    let drawn: Vec<(usize, usize)> = (0 .. 32).flat_map(|y| (0 .. 32).map(move |x| (x, y)))
+        .filter(|&p| canvas[p] != 0.0)
+        .collect();
+    assert_eq!(drawn, vec![(16, 8)]);
+}
+
+#[test]
+fn test_contour_grid() {
+    let grid = |w, h, plot_depth, search_depth| {
+        let options: ContourOptions<f32> = ContourOptions { plot_depth: plot_depth, search_depth: search_depth, .. Default::default() };
+        contour_grid(w, h, &options)
+    };
+    
+    // cells of one pixel, in tiles covering the shorter side
+    assert_eq!(grid(512, 512, None, None), (512, 9, 4));
+    assert_eq!(grid(4096, 1024, None, None), (1024, 10, 5));
+    assert_eq!(grid(600, 300, None, None), (512, 9, 4));
+    
+    // the depths as given, with the search depth at most the plot depth
+    assert_eq!(grid(512, 512, Some(6), None), (512, 6, 3));
+    assert_eq!(grid(512, 512, Some(6), Some(2)), (512, 6, 2));
+    assert_eq!(grid(512, 512, Some(6), Some(8)), (512, 6, 6));
+    assert_eq!(grid(600, 300, None, Some(7)), (512, 9, 7));
+}
+
+#[test]
+fn test_contour_levels() {
+    // circles around the center
+    let f = |T2(x, y): T2<f32, f32>| (x - 0.5) * (x - 0.5) + (y - 0.5) * (y - 0.5);
+    let (levels, weights) = ([0.02, 0.06, 0.15], [1.0, 0.5, 2.0]);
+    let new = || -> Figure<f32, Array<Vec<f32>, RowMajor>> { Figure::new(0.0 .. 1.0, 0.0 .. 1.0, (64, 64)) };
+    
+    let mut once = new();
+    once.contour_levels(f, &levels, Some(&weights));
+    
+    let mut each = new();
+    let mut evaluations = 0;
+    for (&level, &weight) in levels.iter().zip(weights.iter()) {
+        each.contour_levels(f, &[level], Some(&[weight]));
+        evaluations += each.contour_stats().evaluations;
+    }
+    
+    // the same contours
+    let (a, b) = (once.canvas(), each.canvas());
+    let mut total = 0.0;
+    for y in 0 .. 64 {
+        for x in 0 .. 64 {
+            assert!((a[(x, y)] - b[(x, y)]).abs() < 1e-4, "pixel ({}, {}): {} instead of {}", x, y, a[(x, y)], b[(x, y)]);
+            total += a[(x, y)];
+        }
+    }
+    assert!(total > 100.0);
+    
+    // with fewer evaluations, since the levels share their grid points
+    let one_pass = once.contour_stats().evaluations;
+    assert!(one_pass < evaluations, "{} evaluations in one pass, {} one level at a time", one_pass, evaluations);
+}
+
+// for contour_gradient: start points evenly spaced along y = 0.5, and the same value for the offset within the pixel
+#[cfg(test)]
+struct Steps {
+    i: usize,
+    n: usize
+}
+#[cfg(test)]
+impl VRng<T2<f32, f32>> for Steps {
+    fn next(&mut self) -> T2<f32, f32> {
+        let u = ((self.i / 2 % self.n) as f32 + 0.5) / self.n as f32;
+        self.i += 1;
+        T2(u, 0.5)
+    }
+}
+
+#[test]
+fn test_gradient_acceptance() {
+    // Newton steps for x / sqrt(1 + x²) go from x to -x³: they converge to x = 0 from |x| < 1,
+    // and diverge to NaN from |x| > 1
+    let f = |T2(x, _): T2<f32, f32>| (x / (1.0 + x * x).sqrt(), T2((1.0 + x * x) * (1.0 + x * x).sqrt(), 0.0));
+    let run = |x: Range<f32>, acceptance: Acceptance| {
+        let mut fig: Figure<f32, Array<Vec<f32>, RowMajor>> = Figure::new(x, 0.0 .. 1.0, (64, 64));
+        fig.contour_gradient_with(f, 50, 16, acceptance, &mut Steps { i: 0, n: 50 });
+        let canvas = fig.canvas();
+        let total: f32 = (0 .. 64).flat_map(|y| (0 .. 64).map(move |x| (x, y))).map(|p| canvas[p]).sum();
+        (fig.gradient_stats(), total)
+    };
+    
+    // from x = -2.06 to 1.86 in steps of 0.08: 25 start points converge to the zero in the canvas
+    let stats = |accepted, off_canvas| GradientStats { samples: 50, accepted: accepted, off_canvas: off_canvas };
+    assert_eq!(run(-2.1 .. 1.9, Acceptance::All), (stats(25, 25), 25.0));
+    assert_eq!(run(-2.1 .. 1.9, Acceptance::Threshold(1e-3)), (stats(25, 0), 25.0));
+    let (weighted, total) = run(-2.1 .. 1.9, Acceptance::Weighted(1e-3));
+    assert_eq!(weighted, stats(25, 0));
+    assert!(total > 24.9 && total <= 25.0, "{}", total);
+    
+    // from x = 0.29 in steps of 0.08: 9 start points converge, to the zero left of the canvas
+    assert_eq!(run(0.25 .. 4.25, Acceptance::Threshold(1e-3)), (stats(0, 9), 0.0));
+}
@@ -0,0 +1,111 @@
+use std::marker::PhantomData;
+use tuple::T2;
+use math::real::Real;
+#[cfg(target_feature = "avx")]
+use simd::x86::avx::f32x8;
+
+/// Most points `ContourPlot` hands to `sample_batch` at once: the split points of four sibling cells.
+pub const BATCH: usize = 20;
+
+/// A function of a point, that may evaluate many points faster than one by one.
+///
+/// Every `Fn(T2<N, N>) -> N` is one.
+pub trait Sampler<N> {
+    fn sample(&self, p: T2<N, N>) -> N;
+
+    /// Evaluate all `points` into `out`, which has the same length.
+    fn sample_batch(&self, points: &[T2<N, N>], out: &mut [N]) {
+        for (p, v) in points.iter().zip(out.iter_mut()) {
+            *v = self.sample(*p);
+        }
+    }
+}
+
+impl<N, F> Sampler<N> for F where F: Fn(T2<N, N>) -> N {
+    #[inline]
+    fn sample(&self, p: T2<N, N>) -> N {
+        self(p)
+    }
+}
+
+/// A `Real` with several values (its `values`), like `f32x8`.
+pub trait Load: Real {
+    /// number of values, at most 16
+    const LANES: usize;
+
+    /// The value with `values` (`LANES` of them) in order.
+    fn load(values: &[Self::Scalar]) -> Self;
+}
+
+impl Load for f32 {
+    const LANES: usize = 1;
+    fn load(values: &[f32]) -> f32 {
+        values[0]
+    }
+}
+impl Load for f64 {
+    const LANES: usize = 1;
+    fn load(values: &[f64]) -> f64 {
+        values[0]
+    }
+}
+#[cfg(target_feature = "avx")]
+impl Load for f32x8 {
+    const LANES: usize = 8;
+    fn load(values: &[f32]) -> f32x8 {
+        f32x8::load(values, 0)
+    }
+}
+
+/// Evaluate a function over a `Real` with several values for that many points at once.
+pub struct Lanes<V, F> {
+    pub func: F,
+    lanes: PhantomData<V>
+}
+impl<V: Load, F> Lanes<V, F> where F: Fn(T2<V, V>) -> V {
+    pub fn new(func: F) -> Lanes<V, F> {
+        assert!(V::LANES > 0 && V::LANES <= 16);
+        Lanes { func: func, lanes: PhantomData }
+    }
+}
+
+impl<V, F> Sampler<V::Scalar> for Lanes<V, F>
+    where V: Load, V::Scalar: Copy, F: Fn(T2<V, V>) -> V
+{
+    fn sample(&self, T2(x, y): T2<V::Scalar, V::Scalar>) -> V::Scalar {
+        (self.func)(T2(V::splat(x), V::splat(y))).values().next().unwrap()
+    }
+
+    fn sample_batch(&self, points: &[T2<V::Scalar, V::Scalar>], out: &mut [V::Scalar]) {
+        for (points, out) in points.chunks(V::LANES).zip(out.chunks_mut(V::LANES)) {
+            // unused lanes repeat the first point
+            let (mut xs, mut ys) = ([points[0].0; 16], [points[0].1; 16]);
+            for (i, &T2(x, y)) in points.iter().enumerate() {
+                xs[i] = x;
+                ys[i] = y;
+            }
+            let v = (self.func)(T2(V::load(&xs[.. V::LANES]), V::load(&ys[.. V::LANES])));
+            for (o, v) in out.iter_mut().zip(v.values()) {
+                *o = v;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn check_batch<S: Sampler<f32>>(s: &S) {
+    // 11 points: a full chunk of 8 and a partly filled one
+    let points: Vec<T2<f32, f32>> = (0 .. 11).map(|i| T2(i as f32 * 0.7 - 3.0, 2.0 - i as f32 * 0.3)).collect();
+    let mut out = vec![0.0; points.len()];
+    s.sample_batch(&points, &mut out);
+    for (&p, &v) in points.iter().zip(out.iter()) {
+        assert_eq!(v, s.sample(p), "at {:?}", p);
+    }
+}
+
+#[test]
+fn test_lanes() {
+    check_batch(&Lanes::<f32, _>::new(|T2(x, y): T2<f32, f32>| x * x - y.sin()));
+    #[cfg(target_feature = "avx")]
+    check_batch(&Lanes::<f32x8, _>::new(|T2(x, y): T2<f32x8, f32x8>| x * x - y.sin()));
+}
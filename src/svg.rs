@@ -0,0 +1,370 @@
+use std::f64::consts::PI;
+use tuple::T2;
+use math::real::Real;
+use math::cast::Cast;
+use pen::Pen;
+use transform::Transform;
+
+// path data is read and processed in double precision
+type U = f64;
+
+/// Errors while reading SVG path data. The `usize` is the byte offset into the string.
+#[derive(Debug, PartialEq)]
+pub enum PathError {
+    UnknownCommand(usize, char),
+    ExpectedCommand(usize),
+    ExpectedNumber(usize),
+    ExpectedFlag(usize),
+    MissingMoveTo(usize)
+}
+
+struct Parser<'a> {
+    s:      &'a [u8],
+    pos:    usize
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Parser<'a> {
+        Parser { s: s.as_bytes(), pos: 0 }
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(&c) = self.s.get(self.pos) {
+            match c {
+                b' ' | b'\t' | b'\r' | b'\n' | b',' => self.pos += 1,
+                _ => break
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.s.get(self.pos).cloned()
+    }
+
+    fn sign(&mut self) {
+        match self.s.get(self.pos) {
+            Some(&b'-') | Some(&b'+') => self.pos += 1,
+            _ => ()
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(&b'0' ... b'9') = self.s.get(self.pos) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    // numbers may follow each other without separator, as in "1.5.5-2"
+    fn number(&mut self) -> Result<U, PathError> {
+        self.skip_separators();
+        let start = self.pos;
+
+        self.sign();
+        let mut n = self.digits();
+        if let Some(&b'.') = self.s.get(self.pos) {
+            self.pos += 1;
+            n += self.digits();
+        }
+        if n == 0 {
+            self.pos = start;
+            return Err(PathError::ExpectedNumber(start));
+        }
+
+        // an "e" without digits is not part of the number
+        match self.s.get(self.pos) {
+            Some(&b'e') | Some(&b'E') => {
+                let mantissa_end = self.pos;
+                self.pos += 1;
+                self.sign();
+                if self.digits() == 0 {
+                    self.pos = mantissa_end;
+                }
+            },
+            _ => ()
+        }
+
+        // only ASCII has been consumed
+        let text = ::std::str::from_utf8(&self.s[start .. self.pos]).unwrap();
+        text.parse().map_err(|_| PathError::ExpectedNumber(start))
+    }
+
+    fn point(&mut self) -> Result<T2<U, U>, PathError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(T2(x, y))
+    }
+
+    // arc flags are a single digit and need no separator
+    fn flag(&mut self) -> Result<bool, PathError> {
+        match self.peek() {
+            Some(b'0') => { self.pos += 1; Ok(false) },
+            Some(b'1') => { self.pos += 1; Ok(true) },
+            _ => Err(PathError::ExpectedFlag(self.pos))
+        }
+    }
+}
+
+/// Draw the SVG path data `d` with `pen`, mapping all coordinates through `transform`.
+///
+/// Supports all commands of the SVG 1.1 path grammar, absolute and relative.
+/// On an error, the path up to it is drawn.
+pub fn draw_path<F, N>(pen: &mut Pen<F, N>, d: &str, transform: &Transform<N>) -> Result<(), PathError>
+    where F: FnMut(T2<isize, isize>, N),
+          N: Real + PartialOrd + Cast<isize> + Cast<usize>,
+          usize: Cast<N>, isize: Cast<N>, U: Cast<N>
+{
+    let result = commands(pen, d, transform);
+    pen.flush();
+    result
+}
+
+fn commands<F, N>(pen: &mut Pen<F, N>, d: &str, transform: &Transform<N>) -> Result<(), PathError>
+    where F: FnMut(T2<isize, isize>, N),
+          N: Real + PartialOrd + Cast<isize> + Cast<usize>,
+          usize: Cast<N>, isize: Cast<N>, U: Cast<N>
+{
+    let mut parser = Parser::new(d);
+    let to_pen = |p: T2<U, U>| {
+        let p: T2<N, N> = p.cast().unwrap();
+        transform.apply(p)
+    };
+
+    // all in user space
+    let mut current = T2(0.0, 0.0);
+    let mut start = T2(0.0, 0.0);
+    let mut cubic_ctrl = None; // second control point of the last C or S
+    let mut quad_ctrl = None;  // control point of the last Q or T
+    let mut command = None;
+    let mut started = false;
+
+    while let Some(c) = parser.peek() {
+        let pos = parser.pos;
+        let cmd = if c.is_ascii_alphabetic() {
+            parser.pos += 1;
+            c
+        } else {
+            match command {
+                // coordinates after a moveto are implicit lineto commands
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                // closepath takes no arguments
+                Some(b'Z') | Some(b'z') => return Err(PathError::ExpectedCommand(pos)),
+                Some(c) => c,
+                None => return Err(PathError::MissingMoveTo(pos))
+            }
+        };
+        if !started && cmd != b'M' && cmd != b'm' {
+            return Err(PathError::MissingMoveTo(pos));
+        }
+        started = true;
+
+        let relative = cmd.is_ascii_lowercase();
+        let base = if relative { current } else { T2(0.0, 0.0) };
+
+        let mut next_cubic = None;
+        let mut next_quad = None;
+
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                current = base + parser.point()?;
+                start = current;
+                pen.move_to(to_pen(current));
+            },
+            b'L' => {
+                current = base + parser.point()?;
+                pen.line_to(to_pen(current));
+            },
+            b'H' => {
+                current = T2(base.0 + parser.number()?, current.1);
+                pen.line_to(to_pen(current));
+            },
+            b'V' => {
+                current = T2(current.0, base.1 + parser.number()?);
+                pen.line_to(to_pen(current));
+            },
+            b'C' | b'S' => {
+                let c1 = if cmd.to_ascii_uppercase() == b'C' {
+                    base + parser.point()?
+                } else {
+                    // reflection of the previous control point
+                    cubic_ctrl.map(|c| current * 2.0 - c).unwrap_or(current)
+                };
+                let c2 = base + parser.point()?;
+                current = base + parser.point()?;
+                pen.cubic_to(to_pen(c1), to_pen(c2), to_pen(current));
+                next_cubic = Some(c2);
+            },
+            b'Q' | b'T' => {
+                let c = if cmd.to_ascii_uppercase() == b'Q' {
+                    base + parser.point()?
+                } else {
+                    quad_ctrl.map(|c| current * 2.0 - c).unwrap_or(current)
+                };
+                current = base + parser.point()?;
+                pen.quad_to(to_pen(c), to_pen(current));
+                next_quad = Some(c);
+            },
+            b'A' => {
+                let rx = parser.number()?;
+                let ry = parser.number()?;
+                let phi = parser.number()?;
+                let large = parser.flag()?;
+                let sweep = parser.flag()?;
+                let end = base + parser.point()?;
+                arc(pen, &to_pen, current, T2(rx, ry), phi.to_radians(), large, sweep, end);
+                current = end;
+            },
+            b'Z' => {
+                pen.close_path();
+                current = start;
+            },
+            _ => return Err(PathError::UnknownCommand(pos, cmd as char))
+        }
+
+        cubic_ctrl = next_cubic;
+        quad_ctrl = next_quad;
+        command = Some(cmd);
+    }
+
+    Ok(())
+}
+
+// elliptical arc from p0 to p1, drawn as cubic Béziers of at most 90°
+// https://www.w3.org/TR/SVG11/implnote.html#ArcImplementationNotes
+fn arc<F, N, G>(pen: &mut Pen<F, N>, to_pen: &G, p0: T2<U, U>, r: T2<U, U>, phi: U,
+                large: bool, sweep: bool, p1: T2<U, U>)
+    where F: FnMut(T2<isize, isize>, N),
+          N: Real + PartialOrd + Cast<isize> + Cast<usize>,
+          usize: Cast<N>, isize: Cast<N>,
+          G: Fn(T2<U, U>) -> T2<N, N>
+{
+    if p0 == p1 {
+        return;
+    }
+    let T2(mut rx, mut ry) = r;
+    rx = rx.abs();
+    ry = ry.abs();
+    if rx == 0.0 || ry == 0.0 {
+        pen.line_to(to_pen(p1));
+        return;
+    }
+
+    let (sin, cos) = phi.sin_cos();
+    let T2(dx, dy) = (p0 - p1) * 0.5;
+    let x1 = cos * dx + sin * dy;
+    let y1 = -sin * dx + cos * dy;
+
+    // scale up radii that are too small
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coef = (num / den).max(0.0).sqrt();
+    if large == sweep {
+        coef = -coef;
+    }
+    let cx1 = coef * rx * y1 / ry;
+    let cy1 = -coef * ry * x1 / rx;
+    let T2(mx, my) = (p0 + p1) * 0.5;
+    let center = T2(cos * cx1 - sin * cy1 + mx, sin * cx1 + cos * cy1 + my);
+
+    let theta = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+    let mut delta = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx) - theta;
+    if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    } else if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    }
+
+    // point and tangent on the ellipse
+    let point = |t: U| {
+        let (s, c) = t.sin_cos();
+        let (x, y) = (rx * c, ry * s);
+        center + T2(cos * x - sin * y, sin * x + cos * y)
+    };
+    let tangent = |t: U| {
+        let (s, c) = t.sin_cos();
+        let (x, y) = (-rx * s, ry * c);
+        T2(cos * x - sin * y, sin * x + cos * y)
+    };
+
+    let n = (delta.abs() / (0.5 * PI)).ceil().max(1.0) as usize;
+    let step = delta / n as U;
+    let k = 4.0 / 3.0 * (0.25 * step).tan();
+    for i in 0 .. n {
+        let t0 = theta + step * i as U;
+        let t1 = t0 + step;
+        let a = point(t0);
+        let b = if i + 1 == n { p1 } else { point(t1) };
+        pen.cubic_to(
+            to_pen(a + tangent(t0) * k),
+            to_pen(b - tangent(t1) * k),
+            to_pen(b)
+        );
+    }
+}
+
+#[test]
+fn test_numbers() {
+    let mut p = Parser::new("1.5.5-2e1,3 4e");
+    assert_eq!(p.number(), Ok(1.5));
+    assert_eq!(p.number(), Ok(0.5));
+    assert_eq!(p.number(), Ok(-20.0));
+    assert_eq!(p.number(), Ok(3.0));
+    assert_eq!(p.number(), Ok(4.0));
+    assert_eq!(p.number(), Err(PathError::ExpectedNumber(13)));
+}
+
+#[cfg(test)]
+fn pixels(d: &str) -> Vec<(T2<isize, isize>, f64)> {
+    let mut pixels = Vec::new();
+    {
+        let mut pen = Pen::new(|p, v| pixels.push((p, v)));
+        draw_path(&mut pen, d, &Transform::identity()).unwrap();
+    }
+    pixels
+}
+
+#[test]
+fn test_relative() {
+    let absolute = pixels("M 1 1 L 6 1 L 6 5 L 2 5 Z");
+    assert!(!absolute.is_empty());
+    assert_eq!(pixels("m1 1 l5 0 l0 4 l-4 0 z"), absolute);
+    assert_eq!(pixels("M1 1 H6 V5 H2 Z"), absolute);
+    assert_eq!(pixels("m1 1 h5 v4 h-4 z"), absolute);
+    // coordinates after a move are lines
+    assert_eq!(pixels("M1 1 6 1 6 5 2 5 Z"), absolute);
+}
+
+#[test]
+fn test_reflection() {
+    assert_eq!(pixels("M1 1 C1 5 5 5 5 1 S9 -3 9 1"), pixels("M1 1 C1 5 5 5 5 1 C5 -3 9 -3 9 1"));
+    assert_eq!(pixels("M1 1 Q3 5 5 1 T9 1"), pixels("M1 1 Q3 5 5 1 Q7 -3 9 1"));
+    // without a curve before, the control point is the current point
+    assert_eq!(pixels("M1 1 S5 5 9 1"), pixels("M1 1 C1 1 5 5 9 1"));
+}
+
+#[test]
+fn test_arc() {
+    // half of the circle of radius 3 around (5, 5), through (5, 2)
+    let arc = pixels("M2 5 A3 3 0 0 1 8 5");
+    assert!(arc.iter().all(|&(T2(x, y), _)| {
+        let (dx, dy) = (x as f64 - 5.0, y as f64 - 5.0);
+        ((dx * dx + dy * dy).sqrt() - 3.0).abs() < 1.5 && y <= 6
+    }));
+    let at = |pixels: &[(T2<isize, isize>, f64)], p: T2<isize, isize>| -> f64 {
+        pixels.iter().filter(|&&(q, _)| q == p).map(|&(_, v)| v).sum()
+    };
+    assert!(at(&arc, T2(5, 2)) > 0.5);
+    
+    // the other half with the sweep flag cleared
+    let other = pixels("M2 5 A3 3 0 0 0 8 5");
+    assert!(at(&other, T2(5, 8)) > 0.5);
+}
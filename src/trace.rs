@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use tuple::T2;
+use math::real::Real;
+use math::cast::Cast;
+use pen::Pen;
+
+/// Follows the zero set of a function from seed points, instead of searching a grid like `ContourPlot`.
+///
+/// The function returns its value and `gradient / |gradient|²` (so `p - value * grad_inv` is a Newton step),
+/// like for `Figure::contour_gradient`. Every step goes along the tangent (predictor),
+/// and then back onto the curve by Newton iterations (corrector).
+/// The step size shrinks where the curve bends more than `max_angle`, and grows where it is straight.
+///
+/// All distances are in pixels.
+#[derive(Copy, Clone, Debug)]
+pub struct Tracer<N> {
+    /// distance of the seed points on a grid over the canvas
+    pub seed_spacing: N,
+    pub min_step: N,
+    pub max_step: N,
+    /// largest change of direction in one step, in radians
+    pub max_angle: N,
+    /// Newton iterations end when the correction is smaller than this
+    pub tolerance: N,
+    /// longest path from one seed
+    pub max_steps: usize
+}
+
+fn dot<N: Real>(a: T2<N, N>, b: T2<N, N>) -> N {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn norm<N: Real>(a: T2<N, N>) -> N {
+    dot(a, a).sqrt()
+}
+
+// unit tangent from the (inverse) gradient, None where there is none
+fn tangent<N: Real + PartialOrd>(g: T2<N, N>) -> Option<T2<N, N>> {
+    let l = norm(g);
+    if l > N::int(0) && l - l == N::int(0) {
+        Some(T2(N::int(0) - g.1, g.0) * (N::int(1) / l))
+    } else {
+        None
+    }
+}
+
+struct Path<N> {
+    points: Vec<T2<N, N>>,
+    closed: bool
+}
+
+impl<N: Real> Default for Tracer<N> {
+    fn default() -> Tracer<N> {
+        Tracer {
+            seed_spacing: N::int(16),
+            min_step: N::int(1) / N::int(16),
+            max_step: N::int(8),
+            max_angle: N::int(1) / N::int(10),
+            tolerance: N::int(1) / N::int(100),
+            max_steps: 100_000
+        }
+    }
+}
+
+impl<N> Tracer<N> where
+    N: Real + PartialOrd + Cast<isize> + Cast<usize>,
+    usize: Cast<N>, isize: Cast<N>
+{
+    // Newton iterations from p, None if they don't converge
+    fn project<F>(&self, func: &F, mut p: T2<N, N>) -> Option<(T2<N, N>, T2<N, N>)>
+        where F: Fn(T2<N, N>) -> (N, T2<N, N>)
+    {
+        for _ in 0 .. 8 {
+            let (v, g) = func(p);
+            let d = g * v;
+            p = p - d;
+            if norm(d) < self.tolerance {
+                return Some((p, g));
+            }
+        }
+        None
+    }
+
+    // one step of length h (or shorter) from p in direction t
+    fn step<F>(&self, func: &F, p: T2<N, N>, t: T2<N, N>, mut h: N) -> Option<(T2<N, N>, T2<N, N>, N)>
+        where F: Fn(T2<N, N>) -> (N, T2<N, N>)
+    {
+        let min_cos = self.max_angle.cos();
+        while h >= self.min_step {
+            if let Some((q, g)) = self.project(func, p + t * h) {
+                if let Some(tn) = tangent(g) {
+                    // keep going the same way
+                    let tn = if dot(t, tn) < N::int(0) { T2(N::int(0), N::int(0)) - tn } else { tn };
+                    if dot(t, tn) >= min_cos {
+                        return Some((q, tn, h));
+                    }
+                }
+            }
+            h = h / N::int(2);
+        }
+        None
+    }
+
+    /// Trace the curves within `min` .. `max` and draw them with `pen`.
+    pub fn trace<F, D>(&self, func: F, pen: &mut Pen<D, N>, min: T2<N, N>, max: T2<N, N>)
+        where F: Fn(T2<N, N>) -> (N, T2<N, N>), D: FnMut(T2<isize, isize>, N)
+    {
+        // cells of max_step, with the path and point that went through last
+        let mut occupied: HashMap<(isize, isize), (usize, usize)> = HashMap::new();
+        let cell_size = self.max_step;
+        let cell = |p: T2<N, N>| -> (isize, isize) {
+            let T2(x, y) = p * (N::int(1) / cell_size);
+            (x.floor().cast().unwrap(), y.floor().cast().unwrap())
+        };
+        let inside = |p: T2<N, N>| p.0 >= min.0 && p.0 <= max.0 && p.1 >= min.1 && p.1 <= max.1;
+
+        let T2(w, h) = max - min;
+        let nx: usize = (w / self.seed_spacing).floor().cast().unwrap();
+        let ny: usize = (h / self.seed_spacing).floor().cast().unwrap();
+        let mut id = 0;
+        for iy in 0 ... ny {
+            for ix in 0 ... nx {
+                let s: T2<N, N> = T2(ix, iy).cast().unwrap();
+                let (seed, g) = match self.project(&func, min + s * self.seed_spacing) {
+                    Some(r) => r,
+                    None => continue
+                };
+                if !inside(seed) || occupied.contains_key(&cell(seed)) {
+                    continue;
+                }
+                let t = match tangent(g) {
+                    Some(t) => t,
+                    None => continue
+                };
+
+                id += 1;
+                let forward = self.follow(&func, seed, t, id, &mut occupied, &cell, &inside);
+                let points = if forward.closed {
+                    forward.points
+                } else {
+                    let backward = self.follow(&func, seed, T2(N::int(0), N::int(0)) - t, id, &mut occupied, &cell, &inside);
+                    let mut points = backward.points;
+                    points.reverse();
+                    points.extend(forward.points.into_iter().skip(1));
+                    points
+                };
+
+                pen.move_to(points[0]);
+                for &p in &points[1 ..] {
+                    pen.line_to(p);
+                }
+                if forward.closed {
+                    pen.close_path();
+                }
+            }
+        }
+        pen.flush();
+    }
+
+    // the path from seed in direction t, until it leaves the area, closes, or runs into a traced curve
+    fn follow<F, C, I>(&self, func: &F, seed: T2<N, N>, t: T2<N, N>, id: usize,
+                       occupied: &mut HashMap<(isize, isize), (usize, usize)>, cell: &C, inside: &I) -> Path<N>
+        where F: Fn(T2<N, N>) -> (N, T2<N, N>), C: Fn(T2<N, N>) -> (isize, isize), I: Fn(T2<N, N>) -> bool
+    {
+        let half = N::int(1) / N::int(2);
+        let grow = N::int(3) / N::int(2);
+        let near_seed = self.max_step * N::int(2);
+
+        let mut points = vec![seed];
+        let (mut p, mut t, mut h) = (seed, t, self.max_step);
+        for index in 1 .. self.max_steps {
+            let (q, tn, h_used) = match self.step(func, p, t, h) {
+                Some(s) => s,
+                None => break
+            };
+            if !inside(q) {
+                break;
+            }
+            let from_seed = norm(q - seed);
+            if index > 2 && from_seed <= h_used {
+                return Path { points: points, closed: true };
+            }
+            // the cells near the seed belong to both directions
+            if from_seed > near_seed {
+                if let Some(&(path, last)) = occupied.get(&cell(q)) {
+                    if path != id || last + 4 < index {
+                        points.push(q);
+                        break;
+                    }
+                }
+            }
+
+            // mark the cells along the step
+            let n: usize = (norm(q - p) / (self.max_step * half)).floor().cast().unwrap();
+            for i in 0 ... n + 1 {
+                let f: N = i.cast().unwrap();
+                let n1: N = (n + 1).cast().unwrap();
+                occupied.insert(cell(p + (q - p) * (f / n1)), (id, index));
+            }
+
+            points.push(q);
+            p = q;
+            // straight enough to go faster
+            h = if dot(t, tn) >= (self.max_angle * half).cos() {
+                let longer = h_used * grow;
+                if longer > self.max_step { self.max_step } else { longer }
+            } else {
+                h_used
+            };
+            t = tn;
+        }
+        Path { points: points, closed: false }
+    }
+}
+
+#[test]
+fn test_trace_circle() {
+    // circle of radius 20 around (50, 50)
+    let circle = |p: T2<f64, f64>| {
+        let d = p - T2(50., 50.);
+        let r = norm(d);
+        (r - 20., d * (1. / r))
+    };
+    let tracer: Tracer<f64> = Tracer::default();
+    let mut occupied = HashMap::new();
+    let cell = |p: T2<f64, f64>| ((p.0 / 8.).floor() as isize, (p.1 / 8.).floor() as isize);
+    let inside = |p: T2<f64, f64>| p.0 >= 0. && p.0 <= 100. && p.1 >= 0. && p.1 <= 100.;
+
+    let (seed, g) = tracer.project(&circle, T2(75., 50.)).unwrap();
+    let path = tracer.follow(&circle, seed, tangent(g).unwrap(), 1, &mut occupied, &cell, &inside);
+    assert!(path.closed);
+    for p in path.points {
+        assert!((norm(p - T2(50., 50.)) - 20.).abs() < 0.01);
+    }
+
+    let mut drawn = 0.;
+    tracer.trace(circle, &mut Pen::new(|_: T2<isize, isize>, v: f64| drawn += v), T2(0., 0.), T2(100., 100.));
+    // about once around
+    assert!((drawn / (40. * ::std::f64::consts::PI) - 1.).abs() < 0.2);
+}
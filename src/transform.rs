@@ -0,0 +1,104 @@
+use tuple::T2;
+use math::real::Real;
+use util;
+
+/// Affine map, with the same layout as the SVG `matrix(a b c d e f)`:
+///
+///   x' = a x + c y + e
+///   y' = b x + d y + f
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform<N: Real = f32> {
+    pub a: N,
+    pub b: N,
+    pub c: N,
+    pub d: N,
+    pub e: N,
+    pub f: N
+}
+
+impl<N: Real> Transform<N> {
+    pub fn new(a: N, b: N, c: N, d: N, e: N, f: N) -> Transform<N> {
+        Transform { a: a, b: b, c: c, d: d, e: e, f: f }
+    }
+
+    pub fn identity() -> Transform<N> {
+        let (zero, one) = (N::int(0), N::int(1));
+        Transform::new(one, zero, zero, one, zero, zero)
+    }
+
+    pub fn translate(x: N, y: N) -> Transform<N> {
+        let (zero, one) = (N::int(0), N::int(1));
+        Transform::new(one, zero, zero, one, x, y)
+    }
+
+    pub fn scale(x: N, y: N) -> Transform<N> {
+        let zero = N::int(0);
+        Transform::new(x, zero, zero, y, zero, zero)
+    }
+
+    /// Counter-clockwise rotation by `angle` (in radians) around the origin.
+    pub fn rotate(angle: N) -> Transform<N> {
+        let (sin, cos) = (angle.sin(), angle.cos());
+        Transform::new(cos, sin, N::int(0) - sin, cos, N::int(0), N::int(0))
+    }
+
+    /// Shear by the angles `x` (along the x axis) and `y` (along the y axis), in radians.
+    pub fn skew(x: N, y: N) -> Transform<N> {
+        let (zero, one) = (N::int(0), N::int(1));
+        Transform::new(one, y.sin() / y.cos(), x.sin() / x.cos(), one, zero, zero)
+    }
+
+    /// Apply `self` first, then `next`.
+    pub fn then(&self, next: &Transform<N>) -> Transform<N> {
+        Transform::new(
+            next.a * self.a + next.c * self.b,
+            next.b * self.a + next.d * self.b,
+            next.a * self.c + next.c * self.d,
+            next.b * self.c + next.d * self.d,
+            next.a * self.e + next.c * self.f + next.e,
+            next.b * self.e + next.d * self.f + next.f
+        )
+    }
+
+    #[inline]
+    pub fn apply(&self, p: T2<N, N>) -> T2<N, N> {
+        let T2(x, y) = p;
+        T2(
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f
+        )
+    }
+
+    /// Smallest rectangle containing the rectangle `min ... max` after the transform.
+    pub fn apply_rect(&self, min: T2<N, N>, max: T2<N, N>) -> (T2<N, N>, T2<N, N>) where N: PartialOrd {
+        let (mut lo, mut hi) = (self.apply(min), self.apply(min));
+        for &p in &[T2(max.0, min.1), T2(min.0, max.1), max] {
+            let T2(x, y) = self.apply(p);
+            lo = T2(util::min(lo.0, x), util::min(lo.1, y));
+            hi = T2(util::max(hi.0, x), util::max(hi.1, y));
+        }
+        (lo, hi)
+    }
+}
+
+#[test]
+fn test_transform() {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+    let close = |a: T2<f64, f64>, b: T2<f64, f64>| (a.0 - b.0).abs() < 1e-12 && (a.1 - b.1).abs() < 1e-12;
+    let p = T2(1.0, 2.0);
+    
+    // `then` applies self first: translate then rotate, and rotate then translate
+    let (t, r) = (Transform::translate(3.0, 0.0), Transform::rotate(FRAC_PI_2));
+    assert!(close(t.then(&r).apply(p), T2(-2.0, 4.0)));
+    assert!(close(r.then(&t).apply(p), T2(1.0, 1.0)));
+    assert!(close(Transform::identity().then(&t).apply(p), t.apply(p)));
+    
+    assert!(close(Transform::scale(2.0, -1.0).apply(p), T2(2.0, -2.0)));
+    assert!(close(Transform::skew(FRAC_PI_4, 0.0).apply(p), T2(3.0, 2.0)));
+    assert!(close(Transform::skew(0.0, FRAC_PI_4).apply(p), T2(1.0, 3.0)));
+    
+    // the corners of a rotated square
+    let (lo, hi) = Transform::rotate(FRAC_PI_4).apply_rect(T2(0.0, 0.0), T2(1.0, 1.0));
+    let h = 0.5f64.sqrt();
+    assert!(close(lo, T2(-h, 0.0)) && close(hi, T2(h, 2.0 * h)), "{:?} ... {:?}", lo, hi);
+}
@@ -0,0 +1,17 @@
+use math::real::Real;
+
+// the larger of a and b, for types that are only PartialOrd
+#[inline(always)]
+pub fn max<N: PartialOrd>(a: N, b: N) -> N {
+    if a > b { a } else { b }
+}
+
+#[inline(always)]
+pub fn min<N: PartialOrd>(a: N, b: N) -> N {
+    if a < b { a } else { b }
+}
+
+#[inline(always)]
+pub fn ceil<N: Real>(x: N) -> N {
+    N::int(0) - (N::int(0) - x).floor()
+}